@@ -4,8 +4,10 @@ use std::fs::create_dir_all;
 use cosmwasm_schema::{export_schema, remove_schemas, schema_for};
 
 use funding_contract::msg::{
-    CheckDistributionsResponse, CreateProposalResponse, HandleMsg, InitMsg, ProposalListResponse,
-    ProposalStateResponse, QueryMsg, StateResponse,
+    CheckDistributionsResponse, ClaimsResponse, CreateProposalResponse, FinalDistributionsResponse,
+    HandleMsg, InitMsg, PhaseResponse, ProposalListResponse, ProposalStateResponse, QueryMsg,
+    RolesResponse, SponsorsResponse, StateResponse, VestingResponse, VotesByProposalResponse,
+    VotesByVoterResponse,
 };
 use funding_contract::state::State;
 
@@ -20,8 +22,16 @@ fn main() {
     export_schema(&schema_for!(CreateProposalResponse), &out_dir);
     export_schema(&schema_for!(CheckDistributionsResponse), &out_dir);
     export_schema(&schema_for!(QueryMsg), &out_dir);
+    export_schema(&schema_for!(ClaimsResponse), &out_dir);
+    export_schema(&schema_for!(VestingResponse), &out_dir);
+    export_schema(&schema_for!(SponsorsResponse), &out_dir);
+    export_schema(&schema_for!(RolesResponse), &out_dir);
+    export_schema(&schema_for!(FinalDistributionsResponse), &out_dir);
+    export_schema(&schema_for!(PhaseResponse), &out_dir);
     export_schema(&schema_for!(ProposalListResponse), &out_dir);
     export_schema(&schema_for!(ProposalStateResponse), &out_dir);
+    export_schema(&schema_for!(VotesByProposalResponse), &out_dir);
+    export_schema(&schema_for!(VotesByVoterResponse), &out_dir);
     export_schema(&schema_for!(State), &out_dir);
     export_schema(&schema_for!(StateResponse), &out_dir);
 }
@@ -1,20 +1,39 @@
 #![allow(clippy::too_many_arguments)]
 
-use std::collections::HashMap;
-
-use integer_sqrt::IntegerSquareRoot;
+use std::collections::BTreeMap;
 
 use cosmwasm_std::{
-    attr, coin, to_binary, Api, BankMsg, Binary, CanonicalAddr, Coin, CosmosMsg, Env, Extern,
-    HandleResponse, HumanAddr, InitResponse, MessageInfo, Querier, StdError, StdResult, Storage,
+    attr, coin, to_binary, Api, BankMsg, Binary, CanonicalAddr, Coin, CosmosMsg, Decimal, Env,
+    Extern, HandleResponse, HumanAddr, InitResponse, MessageInfo, Order, Querier, ReadonlyStorage,
+    StdError, StdResult, Storage, Uint128, WasmMsg,
 };
+use cosmwasm_storage::{currval, nextval};
 
 use crate::error::ContractError;
+use crate::formula::proposal_votes;
+use crate::merkle;
 use crate::msg::{
-    CheckDistributionsResponse, CreateProposalResponse, HandleMsg, InitMsg, ProposalListResponse,
-    ProposalStateResponse, QueryMsg, StateResponse,
+    CheckDistributionsResponse, ClaimsResponse, CreateProposalResponse, FinalDistributionsResponse,
+    HandleMsg, HypotheticalVote, InitMsg, PhaseResponse, ProposalListResponse,
+    ProposalStateResponse, QueryMsg, RolesResponse, SponsorDeposit, SponsorsResponse,
+    StateResponse, VestingResponse, VoterProof, VotesByProposalResponse, VotesByVoterResponse,
+};
+use crate::phase::{
+    current_phase, period_bound, require_phase, upcoming_deadlines, validate_new_bound,
+    validate_schedule, Expiration, RoundPhase,
 };
-use crate::state::{config, config_read, Distribution, Proposal, State, Vote};
+use crate::state::{
+    claims, claims_read, config, config_read, distribution_snapshot, distribution_snapshot_read,
+    load_all_votes, load_approved_proposals, pool, pool_read, proposal_seq, proposals,
+    proposals_read, refunds, refunds_read, sponsors, sponsors_read, voter_index, voter_index_read,
+    votes, votes_read, Claim, ClaimMilestone, ClaimStatus, Distribution, DistributionSnapshot,
+    FundingFormula, LeftoverPayment, LeftoverPolicy, Milestone, MilestoneStatus, Proposal, Role,
+    State, Vote,
+};
+
+// Pagination defaults for list queries.
+const DEFAULT_LIMIT: u32 = 10;
+const MAX_LIMIT: u32 = 30;
 
 // Note, you can use StdResult in some functions where you do not
 // make use of the custom errors
@@ -24,22 +43,57 @@ pub fn init<S: Storage, A: Api, Q: Querier>(
     info: MessageInfo,
     msg: InitMsg,
 ) -> Result<InitResponse, ContractError> {
-    // let pw: Vec<CanonicalAddr> = msg.proposer_whitelist.into_iter().map(|x| deps.api.canonical_address(&x)).collect::<Vec<CanonicalAddr>>()?;
-    // TODO: this should probably just fail if the user attempts to instantiate the contract
-    // with an address that can't be converted to cannonical form in the whitelists.
-    // Currently it'll just filter out any addresses that fail conversion.
-    let proposer_whitelist: Vec<_> = msg
-        .proposer_whitelist
+    // A typo in a whitelist fails instantiation rather than shrinking the list.
+    let proposer_whitelist =
+        canonical_list(&deps.api, "proposer_whitelist", &msg.proposer_whitelist)?;
+    let voter_whitelist = canonical_list(&deps.api, "voter_whitelist", &msg.voter_whitelist)?;
+    let reviewers = canonical_list(&deps.api, "reviewers", &msg.reviewers)?;
+    let operators = canonical_list(&deps.api, "operators", &msg.operators)?;
+    let treasurers = canonical_list(&deps.api, "treasurers", &msg.treasurers)?;
+
+    if msg.accepted_denoms.is_empty() {
+        return Err(ContractError::NoAcceptedDenoms {});
+    }
+    if let Some(minimum) = msg
+        .min_contribution
         .iter()
-        .map(|x| deps.api.canonical_address(x))
-        .filter_map(Result::ok)
-        .collect();
-    let voter_whitelist: Vec<_> = msg
-        .voter_whitelist
+        .find(|c| !msg.accepted_denoms.contains(&c.denom))
+    {
+        return Err(ContractError::WrongDenom {
+            denom: minimum.denom.clone(),
+        });
+    }
+    // Like FundPool, the pool can only be seeded in denoms votes are matched in.
+    if let Some(c) = info
+        .sent_funds
         .iter()
-        .map(|x| deps.api.canonical_address(x))
-        .filter_map(Result::ok)
-        .collect::<Vec<_>>();
+        .find(|c| !msg.accepted_denoms.contains(&c.denom))
+    {
+        return Err(ContractError::WrongDenom {
+            denom: c.denom.clone(),
+        });
+    }
+    let funding_formula = msg.funding_formula.unwrap_or_default();
+    funding_formula.validate(&msg.accepted_denoms)?;
+    if let Some(root) = &msg.voter_merkle_root {
+        if merkle::parse_hash(root).is_none() {
+            return Err(ContractError::InvalidMerkleRoot {});
+        }
+        // Anyone could vote without a proof and skip the caps in the tree.
+        if msg.open_voting {
+            return Err(ContractError::MerkleRootWithOpenVoting {});
+        }
+    }
+    if let Some(group) = &msg.group {
+        canonical_addr(&deps.api, "group", &group.addr)?;
+    }
+    let leftover_policy = msg.leftover_policy.unwrap_or_default();
+    match &leftover_policy {
+        LeftoverPolicy::Treasury { address } | LeftoverPolicy::Rollover { round: address } => {
+            canonical_addr(&deps.api, "leftover_policy", address)?;
+        }
+        LeftoverPolicy::Sponsors {} => {}
+    }
 
     let state = State {
         // count: msg.count,
@@ -47,17 +101,39 @@ pub fn init<S: Storage, A: Api, Q: Querier>(
         owner: deps.api.canonical_address(&info.sender)?,
         proposer_whitelist,
         voter_whitelist,
+        open_proposals: msg.open_proposals,
+        open_voting: msg.open_voting,
+        voter_merkle_root: msg.voter_merkle_root,
+        group: msg.group,
         // // proposal_min_period: 10,
         // // voting_min_period: 10,
         proposal_period_start: msg.proposal_period_start,
         proposal_period_end: msg.proposal_period_end,
         voting_period_start: msg.voting_period_start,
         voting_period_end: msg.voting_period_end,
-        // funding_formula: Some("QUADRATIC".to_string()),
-        votes: Vec::new(),
-        proposals: Vec::new(),
+        funding_formula,
+        accepted_denoms: msg.accepted_denoms,
+        min_contribution: msg.min_contribution,
+        cancelled: false,
+        claim_deadline: msg.claim_deadline,
+        vesting: msg.vesting,
+        reviewers,
+        require_approval: msg.require_approval,
+        leftover_policy,
+        pending_owner: None,
+        operators,
+        treasurers,
     };
+    validate_schedule(&state)?;
+    if let Some(vesting) = state.vesting {
+        vesting.validate(state.claim_deadline)?;
+    }
     config(&mut deps.storage).save(&state)?;
+    // Funds sent at instantiation seed the matching pool.
+    if !info.sent_funds.is_empty() {
+        sponsors(&mut deps.storage).save(state.owner.as_slice(), &info.sent_funds)?;
+    }
+    pool(&mut deps.storage).save(&info.sent_funds)?;
 
     Ok(InitResponse::default())
     // TODO: handle expired with Err
@@ -72,27 +148,64 @@ pub fn handle<S: Storage, A: Api, Q: Querier>(
 ) -> Result<HandleResponse, ContractError> {
     let state = config_read(&deps.storage).load()?;
     match msg {
-        HandleMsg::StartProposalPeriod { time } => {
-            try_start_proposal_period(deps, env, info, state, time)
+        HandleMsg::StartProposalPeriod { at } => {
+            try_start_proposal_period(deps, env, info, state, at)
         }
-        HandleMsg::EndProposalPeriod { time } => {
-            try_end_proposal_period(deps, env, info, state, time)
-        }
-        HandleMsg::StartVotingPeriod { time } => {
-            try_start_voting_period(deps, env, info, state, time)
-        }
-        HandleMsg::EndVotingPeriod { time } => try_end_voting_period(deps, env, info, state, time),
+        HandleMsg::EndProposalPeriod { at } => try_end_proposal_period(deps, env, info, state, at),
+        HandleMsg::StartVotingPeriod { at } => try_start_voting_period(deps, env, info, state, at),
+        HandleMsg::EndVotingPeriod { at } => try_end_voting_period(deps, env, info, state, at),
         HandleMsg::CreateProposal {
             name,
             description,
             recipient,
             tags,
-        } => try_create_proposal(deps, env, info, state, recipient, name, description, tags),
-        HandleMsg::CreateVote { proposal_id } => {
-            try_create_vote(deps, env, info, state, proposal_id)
+            milestones,
+        } => try_create_proposal(
+            deps,
+            env,
+            info,
+            state,
+            recipient,
+            name,
+            description,
+            tags,
+            milestones.unwrap_or_default(),
+        ),
+        HandleMsg::CreateVote { proposal_id, proof } => {
+            try_create_vote(deps, env, info, state, proposal_id, proof)
         }
+        HandleMsg::FundPool {} => try_fund_pool(deps, env, info, state),
         HandleMsg::CheckDistributions {} => try_check_distributions(deps, env, info, state),
         HandleMsg::DistributeFunds {} => try_distribute_funds(deps, env, info, state),
+        HandleMsg::UpdateProposerWhitelist { add, remove } => {
+            try_update_proposer_whitelist(deps, env, info, state, add, remove)
+        }
+        HandleMsg::UpdateVoterWhitelist { add, remove } => {
+            try_update_voter_whitelist(deps, env, info, state, add, remove)
+        }
+        HandleMsg::ProposeOwner { owner } => try_propose_owner(deps, info, state, owner),
+        HandleMsg::AcceptOwnership {} => try_accept_ownership(deps, info, state),
+        HandleMsg::UpdateRole { role, add, remove } => {
+            try_update_role(deps, info, state, role, add, remove)
+        }
+        HandleMsg::CancelRound {} => try_cancel_round(deps, env, info, state),
+        HandleMsg::ClaimRefund {} => try_claim_refund(deps, env, info, state),
+        HandleMsg::Claim { proposal_id } => try_claim(deps, env, info, state, proposal_id),
+        HandleMsg::WithdrawVested { proposal_id } => {
+            try_withdraw_vested(deps, env, info, state, proposal_id)
+        }
+        HandleMsg::ApproveProposal { proposal_id } => {
+            try_approve_proposal(deps, env, info, state, proposal_id)
+        }
+        HandleMsg::ReleaseMilestone {
+            proposal_id,
+            milestone,
+        } => try_release_milestone(deps, env, info, state, proposal_id, milestone),
+        HandleMsg::ClawBack { proposal_id } => try_claw_back(deps, env, info, state, proposal_id),
+        HandleMsg::ReturnUnclaimed {} => try_return_unclaimed(deps, env, info, state),
+        HandleMsg::SendRollover { to_sponsors } => {
+            try_send_rollover(deps, env, info, state, to_sponsors)
+        }
     }
 }
 
@@ -101,31 +214,20 @@ pub fn try_start_proposal_period<S: Storage, A: Api, Q: Querier>(
     env: Env,
     info: MessageInfo,
     state: State,
-    time: Option<u64>,
+    at: Option<Expiration>,
 ) -> Result<HandleResponse, ContractError> {
-    // Only the contract owner can change periods.
-    let sender_is_valid =
-        validate_sender(deps.api.canonical_address(&info.sender)?, vec![state.owner]);
-    if !sender_is_valid {
-        return Err(ContractError::Unauthorized {
-            list_type: "admin".to_string(),
-        });
-    }
+    // The owner or an operator can change periods.
+    require_role(deps, &info, &state, Role::Operator)?;
     // Proposal period can only start if it hasn't happened yet.
-    let proposal_started = period_started(env.block.time, state.proposal_period_start);
-    if proposal_started {
-        return Err(ContractError::InvalidPeriod {
-            period_type: "pre-proposal".to_string(),
-        });
-    };
+    let phase = current_phase(&deps.storage, &state, &env.block)?;
+    require_phase(phase, &[RoundPhase::Setup])?;
 
-    let start_time = match time {
-        Some(time) => time,
-        None => env.block.time,
-    };
+    let start = period_bound(&state, at, &env.block);
+    validate_new_bound(&start, &env.block)?;
 
     config(&mut deps.storage).update(|mut state| -> Result<State, ContractError> {
-        state.proposal_period_start = Some(start_time);
+        state.proposal_period_start = Some(start);
+        validate_schedule(&state)?;
         Ok(state)
     })?;
     Ok(HandleResponse::default())
@@ -136,36 +238,21 @@ pub fn try_end_proposal_period<S: Storage, A: Api, Q: Querier>(
     env: Env,
     info: MessageInfo,
     state: State,
-    time: Option<u64>,
+    at: Option<Expiration>,
 ) -> Result<HandleResponse, ContractError> {
-    // Only the contract owner can change periods.
-    let sender_is_valid =
-        validate_sender(deps.api.canonical_address(&info.sender)?, vec![state.owner]);
-    if !sender_is_valid {
-        return Err(ContractError::Unauthorized {
-            list_type: "admin".to_string(),
-        });
-    }
+    // The owner or an operator can change periods.
+    require_role(deps, &info, &state, Role::Operator)?;
 
     // Proposal period can only end if it is currently the proposal period.
-    let period_is_valid = validate_period(
-        env.block.time,
-        state.proposal_period_start,
-        state.proposal_period_end,
-    );
-    if !period_is_valid {
-        return Err(ContractError::InvalidPeriod {
-            period_type: "proposal".to_string(),
-        });
-    }
+    let phase = current_phase(&deps.storage, &state, &env.block)?;
+    require_phase(phase, &[RoundPhase::Proposals])?;
 
-    let end_time = match time {
-        Some(time) => time,
-        None => env.block.time,
-    };
+    let end = period_bound(&state, at, &env.block);
+    validate_new_bound(&end, &env.block)?;
 
     config(&mut deps.storage).update(|mut state| -> Result<State, ContractError> {
-        state.proposal_period_end = Some(end_time);
+        state.proposal_period_end = Some(end);
+        validate_schedule(&state)?;
         Ok(state)
     })?;
     Ok(HandleResponse::default())
@@ -176,37 +263,21 @@ pub fn try_start_voting_period<S: Storage, A: Api, Q: Querier>(
     env: Env,
     info: MessageInfo,
     state: State,
-    time: Option<u64>,
+    at: Option<Expiration>,
 ) -> Result<HandleResponse, ContractError> {
-    // Only the contract owner can change periods.
-    let sender_is_valid =
-        validate_sender(deps.api.canonical_address(&info.sender)?, vec![state.owner]);
-    if !sender_is_valid {
-        return Err(ContractError::Unauthorized {
-            list_type: "admin".to_string(),
-        });
-    }
+    // The owner or an operator can change periods.
+    require_role(deps, &info, &state, Role::Operator)?;
 
-    // Voting period can only start if proposal period ended.
-    let proposal_ended = period_ended(env.block.time, state.proposal_period_end);
-
-    // Voting period can only start if it hasn't happened yet.
-    let voting_started = period_started(env.block.time, state.voting_period_start);
-    println!("{} - {}", proposal_ended, voting_started);
-    if !proposal_ended || voting_started {
-        return Err(ContractError::InvalidPeriod {
-            period_type: "pre-voting".to_string(),
-        });
-    };
+    // Voting period can only start after the proposal period, if it hasn't happened yet.
+    let phase = current_phase(&deps.storage, &state, &env.block)?;
+    require_phase(phase, &[RoundPhase::Review])?;
 
-    let start_time = match time {
-        Some(time) => time,
-        None => env.block.time,
-    };
-    println!("at - {}", start_time);
+    let start = period_bound(&state, at, &env.block);
+    validate_new_bound(&start, &env.block)?;
 
     config(&mut deps.storage).update(|mut state| -> Result<State, ContractError> {
-        state.voting_period_start = Some(start_time);
+        state.voting_period_start = Some(start);
+        validate_schedule(&state)?;
         Ok(state)
     })?;
     Ok(HandleResponse::default())
@@ -217,36 +288,21 @@ pub fn try_end_voting_period<S: Storage, A: Api, Q: Querier>(
     env: Env,
     info: MessageInfo,
     state: State,
-    time: Option<u64>,
+    at: Option<Expiration>,
 ) -> Result<HandleResponse, ContractError> {
-    // Only the contract owner can change periods.
-    let sender_is_valid =
-        validate_sender(deps.api.canonical_address(&info.sender)?, vec![state.owner]);
-    if !sender_is_valid {
-        return Err(ContractError::Unauthorized {
-            list_type: "admin".to_string(),
-        });
-    }
+    // The owner or an operator can change periods.
+    require_role(deps, &info, &state, Role::Operator)?;
 
     // Voting period can only end if it is currently the voting period.
-    let period_is_valid = validate_period(
-        env.block.time,
-        state.voting_period_start,
-        state.voting_period_end,
-    );
-    if !period_is_valid {
-        return Err(ContractError::InvalidPeriod {
-            period_type: "voting".to_string(),
-        });
-    }
+    let phase = current_phase(&deps.storage, &state, &env.block)?;
+    require_phase(phase, &[RoundPhase::Voting])?;
 
-    let end_time = match time {
-        Some(time) => time,
-        None => env.block.time,
-    };
+    let end = period_bound(&state, at, &env.block);
+    validate_new_bound(&end, &env.block)?;
 
     config(&mut deps.storage).update(|mut state| -> Result<State, ContractError> {
-        state.voting_period_end = Some(end_time);
+        state.voting_period_end = Some(end);
+        validate_schedule(&state)?;
         Ok(state)
     })?;
     Ok(HandleResponse::default())
@@ -263,39 +319,36 @@ pub fn try_create_proposal<S: Storage, A: Api, Q: Querier>(
     name: String,
     description: String,
     tags: String,
+    milestones: Vec<Milestone>,
 ) -> Result<HandleResponse, ContractError> {
     let sender_addr = deps.api.canonical_address(&info.sender)?;
     let recipient_addr = deps.api.canonical_address(&recipient)?;
-    let sender_is_valid = validate_sender(sender_addr, state.proposer_whitelist);
+    let sender_is_valid = state.open_proposals
+        || validate_sender(sender_addr, state.proposer_whitelist.clone())
+        || is_group_member(deps, &state, &info.sender)?;
     if !sender_is_valid {
         return Err(ContractError::Unauthorized {
             list_type: "proposer".to_string(),
         });
     }
-    let period_is_valid = validate_period(
-        env.block.time,
-        state.proposal_period_start,
-        state.proposal_period_end,
-    );
-    if !period_is_valid {
-        return Err(ContractError::InvalidPeriod {
-            period_type: "proposal".to_string(),
-        });
-    }
-    let proposal_id = state.proposals.len() as u32;
-    if sender_is_valid && period_is_valid {
-        config(&mut deps.storage).update(|mut state| -> Result<State, ContractError> {
-            // state.count += 1;
-            state.proposals.push(Proposal {
-                id: proposal_id,
-                name,
-                description,
-                tags,
-                recipient: recipient_addr,
-            });
-            Ok(state)
-        })?;
-    }
+    let phase = current_phase(&deps.storage, &state, &env.block)?;
+    require_phase(phase, &[RoundPhase::Proposals])?;
+    validate_milestones(&milestones)?;
+    let mut seq = proposal_seq(&mut deps.storage);
+    let proposal_id = currval(&seq)? as u32;
+    nextval(&mut seq)?;
+    proposals(&mut deps.storage).save(
+        &proposal_id.to_be_bytes(),
+        &Proposal {
+            id: proposal_id,
+            name,
+            description,
+            tags,
+            recipient: recipient_addr,
+            milestones,
+            approved: !state.require_approval,
+        },
+    )?;
 
     let res = HandleResponse {
         messages: vec![],
@@ -305,363 +358,1410 @@ pub fn try_create_proposal<S: Storage, A: Api, Q: Querier>(
     Ok(res)
 }
 
-pub fn period_started(time: u64, period_start: Option<u64>) -> bool {
-    match period_start {
-        Some(start) => time >= start,
-        None => false,
+pub fn try_approve_proposal<S: Storage, A: Api, Q: Querier>(
+    deps: &mut Extern<S, A, Q>,
+    env: Env,
+    info: MessageInfo,
+    state: State,
+    proposal_id: u32,
+) -> Result<HandleResponse, ContractError> {
+    // The owner or a reviewer can approve proposals, until voting ends.
+    require_role(deps, &info, &state, Role::Reviewer)?;
+    let phase = current_phase(&deps.storage, &state, &env.block)?;
+    require_phase(
+        phase,
+        &[
+            RoundPhase::Proposals,
+            RoundPhase::Review,
+            RoundPhase::Voting,
+        ],
+    )?;
+    let key = proposal_id.to_be_bytes();
+    let mut proposal = match proposals_read(&deps.storage).may_load(&key)? {
+        Some(proposal) => proposal,
+        None => return Err(ContractError::InvalidProposal { id: proposal_id }),
+    };
+    if proposal.approved {
+        return Err(ContractError::AlreadyApproved { id: proposal_id });
     }
+    proposal.approved = true;
+    proposals(&mut deps.storage).save(&key, &proposal)?;
+
+    Ok(HandleResponse {
+        messages: vec![],
+        attributes: vec![
+            attr("action", "approve proposal"),
+            attr("proposal_id", proposal_id),
+        ],
+        data: None,
+    })
 }
 
-pub fn period_ended(time: u64, period_end: Option<u64>) -> bool {
-    match period_end {
-        Some(end) => time > end,
-        None => false,
+// Milestones, if any, must each unlock part of the distribution and together
+// unlock all of it.
+pub fn validate_milestones(milestones: &[Milestone]) -> Result<(), ContractError> {
+    if milestones.is_empty() {
+        return Ok(());
+    }
+    if milestones.iter().any(|m| m.share.is_zero()) {
+        return Err(ContractError::InvalidMilestones {
+            reason: "every milestone must have a share greater than 0".to_string(),
+        });
+    }
+    let total = milestones
+        .iter()
+        .fold(Decimal::zero(), |total, m| total + m.share);
+    if total != Decimal::one() {
+        return Err(ContractError::InvalidMilestones {
+            reason: "milestone shares must add up to 1".to_string(),
+        });
     }
+    Ok(())
 }
 
-pub fn validate_period(time: u64, period_start: Option<u64>, period_end: Option<u64>) -> bool {
-    let started = period_started(time, period_start);
-    let ended = period_ended(time, period_end);
-    // period is valid if it started and has not ended.
-    started && !ended
+pub fn validate_sender(addr: CanonicalAddr, list: Vec<CanonicalAddr>) -> bool {
+    list.contains(&addr)
 }
 
-pub fn validate_sender(addr: CanonicalAddr, list: Vec<CanonicalAddr>) -> bool {
-    list.is_empty() || list.contains(&addr)
+// The group is only queried for senders not let in some other way.
+fn is_group_member<S: Storage, A: Api, Q: Querier>(
+    deps: &Extern<S, A, Q>,
+    state: &State,
+    sender: &HumanAddr,
+) -> StdResult<bool> {
+    match &state.group {
+        Some(group) => group.is_member(&deps.querier, sender),
+        None => Ok(false),
+    }
 }
 
-pub fn try_create_vote<S: Storage, A: Api, Q: Querier>(
+fn canonical_addr<A: Api>(
+    api: &A,
+    list: &str,
+    addr: &HumanAddr,
+) -> Result<CanonicalAddr, ContractError> {
+    api.canonical_address(addr)
+        .map_err(|_| ContractError::InvalidAddress {
+            list: list.to_string(),
+            address: addr.to_string(),
+        })
+}
+
+fn canonical_list<A: Api>(
+    api: &A,
+    list: &str,
+    addrs: &[HumanAddr],
+) -> Result<Vec<CanonicalAddr>, ContractError> {
+    addrs
+        .iter()
+        .map(|addr| canonical_addr(api, list, addr))
+        .collect()
+}
+
+pub fn try_update_proposer_whitelist<S: Storage, A: Api, Q: Querier>(
     deps: &mut Extern<S, A, Q>,
     env: Env,
     info: MessageInfo,
-    state: State,
-    proposal_id: u32,
+    mut state: State,
+    add: Vec<HumanAddr>,
+    remove: Vec<HumanAddr>,
+) -> Result<HandleResponse, ContractError> {
+    require_owner(deps, &info, &state)?;
+    let phase = current_phase(&deps.storage, &state, &env.block)?;
+    require_phase(phase, &[RoundPhase::Setup, RoundPhase::Proposals])?;
+
+    let (added, removed) = update_whitelist(
+        deps,
+        "proposer_whitelist",
+        &mut state.proposer_whitelist,
+        add,
+        remove,
+    )?;
+    config(&mut deps.storage).save(&state)?;
+    Ok(HandleResponse {
+        messages: vec![],
+        attributes: vec![
+            attr("action", "update proposer whitelist"),
+            attr("added", added.join(",")),
+            attr("removed", removed.join(",")),
+        ],
+        data: None,
+    })
+}
+
+pub fn try_update_voter_whitelist<S: Storage, A: Api, Q: Querier>(
+    deps: &mut Extern<S, A, Q>,
+    env: Env,
+    info: MessageInfo,
+    mut state: State,
+    add: Vec<HumanAddr>,
+    remove: Vec<HumanAddr>,
 ) -> Result<HandleResponse, ContractError> {
+    require_owner(deps, &info, &state)?;
+    let phase = current_phase(&deps.storage, &state, &env.block)?;
+    require_phase(
+        phase,
+        &[RoundPhase::Setup, RoundPhase::Proposals, RoundPhase::Review],
+    )?;
+
+    let (added, removed) = update_whitelist(
+        deps,
+        "voter_whitelist",
+        &mut state.voter_whitelist,
+        add,
+        remove,
+    )?;
+    config(&mut deps.storage).save(&state)?;
+    Ok(HandleResponse {
+        messages: vec![],
+        attributes: vec![
+            attr("action", "update voter whitelist"),
+            attr("added", added.join(",")),
+            attr("removed", removed.join(",")),
+        ],
+        data: None,
+    })
+}
+
+fn require_owner<S: Storage, A: Api, Q: Querier>(
+    deps: &Extern<S, A, Q>,
+    info: &MessageInfo,
+    state: &State,
+) -> Result<(), ContractError> {
     let sender_is_valid = validate_sender(
         deps.api.canonical_address(&info.sender)?,
-        state.voter_whitelist,
+        vec![state.owner.clone()],
     );
     if !sender_is_valid {
         return Err(ContractError::Unauthorized {
-            list_type: "voter".to_string(),
+            list_type: "admin".to_string(),
         });
     }
-    let period_is_valid = validate_period(
-        env.block.time,
-        state.voting_period_start,
-        state.voting_period_end,
-    );
-    if !period_is_valid {
-        return Err(ContractError::InvalidPeriod {
-            period_type: "voting".to_string(),
+    Ok(())
+}
+
+// The owner can do anything a role can.
+fn require_role<S: Storage, A: Api, Q: Querier>(
+    deps: &Extern<S, A, Q>,
+    info: &MessageInfo,
+    state: &State,
+    role: Role,
+) -> Result<(), ContractError> {
+    let mut allowed = match role {
+        Role::Operator => state.operators.clone(),
+        Role::Treasurer => state.treasurers.clone(),
+        Role::Reviewer => state.reviewers.clone(),
+    };
+    allowed.push(state.owner.clone());
+    if !validate_sender(deps.api.canonical_address(&info.sender)?, allowed) {
+        return Err(ContractError::Unauthorized {
+            list_type: role.as_str().to_string(),
         });
     }
-    let proposal_is_valid = state.proposals.len() as u32 > proposal_id;
-    if !proposal_is_valid {
-        return Err(ContractError::InvalidProposal { id: proposal_id });
-    }
-    let voter = deps.api.canonical_address(&info.sender)?;
-    if sender_is_valid && period_is_valid && proposal_is_valid {
-        config(&mut deps.storage).update(|mut state| -> Result<State, ContractError> {
-            state.votes.push(Vote {
-                voter,
-                proposal: proposal_id,
-                amount: info.sent_funds,
-            });
-            Ok(state)
-        })?;
-    }
+    Ok(())
+}
 
-    Ok(HandleResponse::default())
+pub fn try_propose_owner<S: Storage, A: Api, Q: Querier>(
+    deps: &mut Extern<S, A, Q>,
+    info: MessageInfo,
+    mut state: State,
+    owner: HumanAddr,
+) -> Result<HandleResponse, ContractError> {
+    require_owner(deps, &info, &state)?;
+    state.pending_owner = Some(canonical_addr(&deps.api, "owner", &owner)?);
+    config(&mut deps.storage).save(&state)?;
+    Ok(HandleResponse {
+        messages: vec![],
+        attributes: vec![attr("action", "propose owner"), attr("owner", owner)],
+        data: None,
+    })
 }
 
-pub fn try_check_distributions<S: Storage, A: Api, Q: Querier>(
+// Ownership only moves once the new owner accepts, so it can't be handed to an
+// address nobody controls.
+pub fn try_accept_ownership<S: Storage, A: Api, Q: Querier>(
     deps: &mut Extern<S, A, Q>,
-    env: Env,
-    _info: MessageInfo,
-    state: State,
+    info: MessageInfo,
+    mut state: State,
 ) -> Result<HandleResponse, ContractError> {
-    // Distributions can only be checked after proposal period.
-    let period_is_valid = period_ended(env.block.time, state.proposal_period_end);
-    if !period_is_valid {
-        return Err(ContractError::InvalidPeriod {
-            period_type: "post-proposal".to_string(),
+    let sender = deps.api.canonical_address(&info.sender)?;
+    if state.pending_owner.as_ref() != Some(&sender) {
+        return Err(ContractError::Unauthorized {
+            list_type: "pending owner".to_string(),
         });
     }
-
-    let distributions: Vec<Distribution> = calculate_distributions(
-        state.votes,
-        state.proposals,
-        deps.querier.query_all_balances(&env.contract.address)?,
-        // vec![coin(100_000, "ucosm")],
-    );
-
-    let res = HandleResponse {
+    state.owner = sender;
+    state.pending_owner = None;
+    config(&mut deps.storage).save(&state)?;
+    Ok(HandleResponse {
         messages: vec![],
         attributes: vec![
-            attr(
-                "distributions",
-                to_binary(&CheckDistributionsResponse {
-                    distributions: distributions.clone(),
-                })?,
-            ),
-            attr(
-                "balance",
-                format!(
-                    "{:?}",
-                    deps.querier.query_all_balances(env.contract.address)?
-                ),
-            ),
+            attr("action", "accept ownership"),
+            attr("owner", info.sender),
         ],
-        data: Some(to_binary(&CheckDistributionsResponse { distributions })?),
+        data: None,
+    })
+}
+
+pub fn try_update_role<S: Storage, A: Api, Q: Querier>(
+    deps: &mut Extern<S, A, Q>,
+    info: MessageInfo,
+    mut state: State,
+    role: Role,
+    add: Vec<HumanAddr>,
+    remove: Vec<HumanAddr>,
+) -> Result<HandleResponse, ContractError> {
+    require_owner(deps, &info, &state)?;
+    let members = match role {
+        Role::Operator => &mut state.operators,
+        Role::Treasurer => &mut state.treasurers,
+        Role::Reviewer => &mut state.reviewers,
     };
-    Ok(res)
+    let (added, removed) = update_whitelist(deps, role.as_str(), members, add, remove)?;
+    config(&mut deps.storage).save(&state)?;
+    Ok(HandleResponse {
+        messages: vec![],
+        attributes: vec![
+            attr("action", "update role"),
+            attr("role", role.as_str()),
+            attr("added", added.join(",")),
+            attr("removed", removed.join(",")),
+        ],
+        data: None,
+    })
 }
 
-pub fn try_distribute_funds<S: Storage, A: Api, Q: Querier>(
+// Applies `add` then `remove` to the list, returning the addresses that actually
+// changed so they can be listed in the response.
+fn update_whitelist<S: Storage, A: Api, Q: Querier>(
+    deps: &Extern<S, A, Q>,
+    list_type: &str,
+    list: &mut Vec<CanonicalAddr>,
+    add: Vec<HumanAddr>,
+    remove: Vec<HumanAddr>,
+) -> Result<(Vec<String>, Vec<String>), ContractError> {
+    let mut added = vec![];
+    for addr in add {
+        let canonical = canonical_addr(&deps.api, list_type, &addr)?;
+        if !list.contains(&canonical) {
+            list.push(canonical);
+            added.push(addr.to_string());
+        }
+    }
+    let mut removed = vec![];
+    for addr in remove {
+        let canonical = canonical_addr(&deps.api, list_type, &addr)?;
+        if list.contains(&canonical) {
+            list.retain(|a| *a != canonical);
+            removed.push(addr.to_string());
+        }
+    }
+    Ok((added, removed))
+}
+
+pub fn try_create_vote<S: Storage, A: Api, Q: Querier>(
     deps: &mut Extern<S, A, Q>,
     env: Env,
     info: MessageInfo,
     state: State,
+    proposal_id: u32,
+    proof: Option<VoterProof>,
 ) -> Result<HandleResponse, ContractError> {
-    // Only the contract owner can distribute funds.
-    let sender_is_valid =
-        validate_sender(deps.api.canonical_address(&info.sender)?, vec![state.owner]);
-    if !sender_is_valid {
-        return Err(ContractError::Unauthorized {
-            list_type: "admin".to_string(),
-        });
+    let contribution = validate_contribution(&state, &info.sent_funds)?;
+    let voter = deps.api.canonical_address(&info.sender)?;
+    let cap = match proof {
+        Some(proof) => verify_voter_proof(&state, &info.sender, proof)?,
+        None => {
+            let sender_is_valid = state.open_voting
+                || validate_sender(voter.clone(), state.voter_whitelist.clone())
+                || is_group_member(deps, &state, &info.sender)?;
+            if !sender_is_valid {
+                return Err(ContractError::Unauthorized {
+                    list_type: "voter".to_string(),
+                });
+            }
+            None
+        }
+    };
+    let phase = current_phase(&deps.storage, &state, &env.block)?;
+    require_phase(phase, &[RoundPhase::Voting])?;
+    match proposals_read(&deps.storage).may_load(&proposal_id.to_be_bytes())? {
+        Some(proposal) if !proposal.approved => {
+            return Err(ContractError::ProposalNotApproved { id: proposal_id })
+        }
+        Some(_) => {}
+        None => return Err(ContractError::InvalidProposal { id: proposal_id }),
     }
-    // Distributions can only be checked after proposal period.
-    let period_is_valid = validate_period(env.block.time, state.voting_period_end, Some(u64::MAX));
-    if !period_is_valid {
-        return Err(ContractError::InvalidPeriod {
-            period_type: "voting".to_string(),
-        });
+    if let Some(cap) = cap {
+        check_voter_cap(deps, &voter, &cap, &contribution)?;
     }
+    // Repeat votes by the same voter on a proposal are accumulated into one entry.
+    votes(&mut deps.storage, proposal_id).update(
+        voter.as_slice(),
+        |vote| -> Result<Vote, ContractError> {
+            match vote {
+                Some(mut vote) => {
+                    vote.amount = add_coins(vote.amount, vec![contribution]);
+                    Ok(vote)
+                }
+                None => Ok(Vote {
+                    voter: voter.clone(),
+                    proposal: proposal_id,
+                    amount: vec![contribution],
+                }),
+            }
+        },
+    )?;
+    voter_index(&mut deps.storage, &voter).save(&proposal_id.to_be_bytes(), &proposal_id)?;
 
-    let distributions: Vec<Distribution> = calculate_distributions(
-        state.votes,
-        state.proposals,
-        deps.querier.query_all_balances(&env.contract.address)?,
-    );
-
-    // TODO: Send funds to proposal recipients.
-    send_distributions(deps, env, distributions, &"distribute funds".to_string())
-
-    // TODO: Finalize response data.
-    // Should this return the same Vec<Distribution> data as CheckDistributions?
-    // let res = HandleResponse {
-    //     messages: vec![],
-    //     attributes: vec![],
-    //     data: Some(to_binary(&CheckDistributionsResponse { distributions })?),
-    // };
-    // Ok(res)
-}
-
-pub fn is_coin_micro(denom: &str) -> bool {
-    denom.starts_with('u')
+    Ok(HandleResponse::default())
 }
 
-pub fn get_normalized_votes(votes: &[Vote]) -> Vec<Vote> {
-    let mut unique: HashMap<String, Vote> = HashMap::new();
-    for vote in votes {
-        let tag = format!("{}--{}", vote.voter, vote.proposal.to_string());
-
-        let denom = &vote.amount[0].denom;
-        let mut new_denom = denom.clone();
-        let mut math_factor = 1u128;
-        if !is_coin_micro(denom) {
-            new_denom = format!("{}{}", "u", denom.to_string());
-            math_factor *= 1_000_000u128;
-        }
-
-        let norm_vote = Vote {
-            voter: vote.voter.clone(),
-            proposal: vote.proposal,
-            amount: vec![coin(vote.amount[0].amount.u128() * math_factor, &new_denom)],
-        };
-
-        // by default add the vote itself.
-        let new_entry = if !unique.contains_key(&tag) {
-            norm_vote
-        } else {
-            // if the tag exists, update the existing norm_vote amount.
-            let value = unique.get(&tag).unwrap();
-
-            Vote {
-                voter: norm_vote.voter.clone(),
-                proposal: norm_vote.proposal,
-                amount: vec![coin(
-                    norm_vote.amount[0].amount.u128() + value.amount[0].amount.u128(),
-                    &norm_vote.amount[0].denom,
-                )],
-            }
-        };
-
-        // add new key, or update existing.
-        unique.insert(tag, new_entry);
+// Checks the proof against the round's voter merkle root, returning the cap the
+// voter's leaf commits to.
+fn verify_voter_proof(
+    state: &State,
+    sender: &HumanAddr,
+    proof: VoterProof,
+) -> Result<Option<Coin>, ContractError> {
+    let root = match state
+        .voter_merkle_root
+        .as_deref()
+        .and_then(merkle::parse_hash)
+    {
+        Some(root) => root,
+        None => return Err(ContractError::InvalidMerkleProof {}),
+    };
+    let siblings = proof
+        .proof
+        .iter()
+        .map(|hash| merkle::parse_hash(hash))
+        .collect::<Option<Vec<_>>>()
+        .ok_or(ContractError::InvalidMerkleProof {})?;
+    if !merkle::verify(&root, merkle::leaf(sender, proof.cap.as_ref()), &siblings) {
+        return Err(ContractError::InvalidMerkleProof {});
     }
-    unique.values().cloned().collect()
+    Ok(proof.cap)
 }
 
-pub fn calculate_distributions(
-    votes: Vec<Vote>,
-    proposals: Vec<Proposal>,
-    budget_contstraint: Vec<Coin>,
-) -> Vec<Distribution> {
-    let denom = &budget_contstraint[0].denom;
-    let mut new_denom = denom.clone();
-    let math_factor = 1_000_000u128;
-
-    let mut budget_value = budget_contstraint[0].amount.u128();
-    // Multiply values so that we don't have to convert to floats
-
-    if !is_coin_micro(&budget_contstraint[0].denom) {
-        new_denom = format!("{}{}", "u".to_string(), denom.to_string());
-        budget_value *= math_factor;
-
-        // math_factor *= math_factor;
+// A capped voter's contributions across every proposal can't go over the cap,
+// which is only in one denom.
+fn check_voter_cap<S: Storage, A: Api, Q: Querier>(
+    deps: &Extern<S, A, Q>,
+    voter: &CanonicalAddr,
+    cap: &Coin,
+    contribution: &Coin,
+) -> Result<(), ContractError> {
+    if contribution.denom != cap.denom {
+        return Err(ContractError::WrongDenom {
+            denom: contribution.denom.clone(),
+        });
     }
-
-    // Collapse multiple votes all votes by a single voter for a single proposal
-    let unique_votes = get_normalized_votes(&votes);
-
-    // TODO: convert to same currency? normalize to shell or ushell
-
-    struct DistIdeal {
-        proposal: u32,
-        recipient: CanonicalAddr,
-        votes: Vec<u128>,
-        distribution_ideal: u128,
-        subsidy_ideal: u128,
+    let proposal_ids: Vec<u32> = voter_index_read(&deps.storage, voter)
+        .range(None, None, Order::Ascending)
+        .map(|item| item.map(|(_, proposal_id)| proposal_id))
+        .collect::<StdResult<_>>()?;
+    let mut total = contribution.amount;
+    for proposal_id in proposal_ids {
+        let vote = votes_read(&deps.storage, proposal_id).load(voter.as_slice())?;
+        total += amount_of(&vote.amount, &cap.denom);
     }
+    if total > cap.amount {
+        return Err(ContractError::AboveCap {
+            cap: cap.amount,
+            denom: cap.denom.clone(),
+        });
+    }
+    Ok(())
+}
 
-    let ideal_results: Vec<_> = proposals
-        .into_iter()
-        .map(|p| {
-            // Convert votes to a nicer format
-            let proposal_votes: Vec<u128> = unique_votes
-                .iter()
-                .filter(|v| v.proposal == p.id)
-                .map(|v| v.amount[0].amount.u128())
-                .collect();
-
-            let distribution_ideal: u128 = proposal_votes
-                .iter()
-                .map(|v| v.integer_sqrt())
-                .sum::<u128>()
-                .pow(2);
-
-            let total_votes: u128 = proposal_votes.iter().sum();
-            let subsidy_ideal: u128 = match distribution_ideal > total_votes {
-                true => distribution_ideal - total_votes,
-                false => 0,
-            };
-
-            DistIdeal {
-                proposal: p.id,
-                recipient: p.recipient,
-                votes: proposal_votes,
-                distribution_ideal,
-                subsidy_ideal,
-            }
-        })
-        .collect();
-
-    // let constraint_factor: f64 = ideal_results.iter().map(|x| x.subsidy_ideal).sum::<f64>() / budget_value;
-    let constraint_factor: u128 =
-        math_factor * ideal_results.iter().map(|x| x.subsidy_ideal).sum::<u128>() / budget_value;
-
-    ideal_results
+// Subtracts `minus` from `total` per denom, dropping anything left at zero.
+pub fn sub_coins(total: Vec<Coin>, minus: &[Coin]) -> Vec<Coin> {
+    total
         .into_iter()
-        .map(|p| {
-            // let total_votes: f64 = p.votes.iter().sum();
-            // let distribution_actual: f64 = (p.distribution_ideal - total_votes) / constraint_factor + total_votes;
-            // let subsidy_actual: f64 = distribution_actual - total_votes;
-            let total_votes: u128 = p.votes.iter().sum();
-            // let distribution_actual: u128 = math_factor * (p.distribution_ideal - total_votes)
-            let scary_term: u128 = match p.distribution_ideal > total_votes {
-                true => p.distribution_ideal - total_votes,
-                false => 0,
-            };
-            let distribution_actual: u128 =
-                math_factor * scary_term / constraint_factor + total_votes;
-            let subsidy_actual: u128 = distribution_actual - total_votes;
-            Distribution {
-                proposal: p.proposal,
-                recipient: p.recipient,
-                votes: p.votes.iter().map(|v| coin(*v, &new_denom)).collect(),
-                distribution_ideal: coin(p.distribution_ideal, &new_denom),
-                subsidy_ideal: coin(p.subsidy_ideal, &new_denom),
-                distribution_actual: coin(distribution_actual, &new_denom),
-                subsidy_actual: coin(subsidy_actual, &new_denom),
+        .map(|mut c| {
+            if let Some(m) = minus.iter().find(|m| m.denom == c.denom) {
+                c.amount = Uint128(c.amount.u128().saturating_sub(m.amount.u128()));
             }
+            c
         })
+        .filter(|c| !c.amount.is_zero())
         .collect()
-
-    // vec![Distribution{
-    //     proposal: s,
-    //     votes: vec![coin(1, "SHELL")],
-    //     distribution_ideal: coin(1, "SHELL"),
-    //     distribution_actual: coin(1, "SHELL"),
-    //     subsidy_ideal: coin(1, "SHELL"),
-    //     subsidy_actual: coin(1, "SHELL"),
-    // }]
 }
 
-fn send_distributions<S: Storage, A: Api, Q: Querier>(
-    deps: &mut Extern<S, A, Q>,
-    env: Env,
-    distributions: Vec<Distribution>,
-    action: &str,
-) -> Result<HandleResponse, ContractError> {
-    let attributes = vec![attr("action", action)];
-
-    // // it should cost ~800 ucosm to send a
-    // let send_cost = 1000;
-    let messages = distributions
-        .into_iter()
-        .map(|d| {
-            let contract_address = env.contract.address.clone();
-            let recipient = deps.api.human_address(&d.recipient).unwrap();
-            // let amount_send = match d.distribution_actual.amount.u128() > send_cost {
-            //     true => d.distribution_actual.amount.u128() - send_cost,
-            //     false => 0,
-            // };
-            // if amount_send > 0 {
-            CosmosMsg::Bank(BankMsg::Send {
-                from_address: contract_address,
-                to_address: recipient,
-                amount: vec![d.distribution_actual],
-                // amount: vec![coin(amount_send, "ucosm")],
-                // amount: vec![coin(10, "ucosm")],
-            })
-            // } else {
-            //     None
-            // }
-        })
+// Checks the funds sent with a vote and returns the single coin contributed.
+pub fn validate_contribution(state: &State, sent_funds: &[Coin]) -> Result<Coin, ContractError> {
+    // Each denom is matched separately, so a vote can only carry a single one.
+    if sent_funds.len() > 1 {
+        return Err(ContractError::MultipleDenoms {});
+    }
+    let sent = match sent_funds.first() {
+        Some(sent) if !sent.amount.is_zero() => sent,
+        _ => return Err(ContractError::NoFunds {}),
+    };
+    if !state.accepted_denoms.contains(&sent.denom) {
+        return Err(ContractError::WrongDenom {
+            denom: sent.denom.clone(),
+        });
+    }
+    if let Some(minimum) = state
+        .min_contribution
+        .iter()
+        .find(|c| c.denom == sent.denom)
+    {
+        if sent.amount < minimum.amount {
+            return Err(ContractError::BelowMinimum {
+                minimum: minimum.amount,
+                denom: minimum.denom.clone(),
+            });
+        }
+    }
+    Ok(sent.clone())
+}
+
+pub fn add_coins(mut total: Vec<Coin>, added: Vec<Coin>) -> Vec<Coin> {
+    for c in added {
+        match total.iter_mut().find(|t| t.denom == c.denom) {
+            Some(t) => t.amount += c.amount,
+            None => total.push(c),
+        }
+    }
+    total
+}
+
+pub fn try_fund_pool<S: Storage, A: Api, Q: Querier>(
+    deps: &mut Extern<S, A, Q>,
+    env: Env,
+    info: MessageInfo,
+    state: State,
+) -> Result<HandleResponse, ContractError> {
+    // The pool can grow until funds are distributed.
+    let phase = current_phase(&deps.storage, &state, &env.block)?;
+    require_phase(
+        phase,
+        &[
+            RoundPhase::Setup,
+            RoundPhase::Proposals,
+            RoundPhase::Review,
+            RoundPhase::Voting,
+            RoundPhase::Tally,
+        ],
+    )?;
+    let deposit: Vec<Coin> = info
+        .sent_funds
+        .into_iter()
+        .filter(|c| !c.amount.is_zero())
         .collect();
+    if deposit.is_empty() {
+        return Err(ContractError::NoFunds {});
+    }
+    if let Some(c) = deposit
+        .iter()
+        .find(|c| !state.accepted_denoms.contains(&c.denom))
+    {
+        return Err(ContractError::WrongDenom {
+            denom: c.denom.clone(),
+        });
+    }
+
+    let sponsor = deps.api.canonical_address(&info.sender)?;
+    sponsors(&mut deps.storage).update(sponsor.as_slice(), |existing| -> StdResult<_> {
+        Ok(add_coins(existing.unwrap_or_default(), deposit.clone()))
+    })?;
+    pool(&mut deps.storage)
+        .update(|total| -> StdResult<_> { Ok(add_coins(total, deposit.clone())) })?;
+
+    Ok(HandleResponse {
+        messages: vec![],
+        attributes: vec![
+            attr("action", "fund pool"),
+            attr("amount", format!("{:?}", deposit)),
+        ],
+        data: None,
+    })
+}
+
+// The matching pool in the denoms votes are matched in.
+fn matching_pool<S: ReadonlyStorage>(storage: &S, state: &State) -> StdResult<Vec<Coin>> {
+    let total = pool_read(storage).may_load()?.unwrap_or_default();
+    Ok(accepted_balance(total, &state.accepted_denoms))
+}
+
+pub fn try_check_distributions<S: Storage, A: Api, Q: Querier>(
+    deps: &mut Extern<S, A, Q>,
+    env: Env,
+    _info: MessageInfo,
+    state: State,
+) -> Result<HandleResponse, ContractError> {
+    // Distributions can only be checked after proposal period.
+    let phase = current_phase(&deps.storage, &state, &env.block)?;
+    require_phase(
+        phase,
+        &[RoundPhase::Review, RoundPhase::Voting, RoundPhase::Tally],
+    )?;
+
+    let budget = matching_pool(&deps.storage, &state)?;
+    let distributions: Vec<Distribution> = calculate_distributions(
+        &state.funding_formula,
+        load_all_votes(&deps.storage)?,
+        load_approved_proposals(&deps.storage)?,
+        budget.clone(),
+        // vec![coin(100_000, "ucosm")],
+    )?;
+    let response = CheckDistributionsResponse {
+        dust: calculate_dust(&distributions, &budget)?,
+        distributions,
+    };
+
+    let res = HandleResponse {
+        messages: vec![],
+        attributes: vec![
+            attr("distributions", to_binary(&response)?),
+            attr("pool", format!("{:?}", budget)),
+        ],
+        data: Some(to_binary(&response)?),
+    };
+    Ok(res)
+}
 
-    let r = HandleResponse {
+pub fn try_distribute_funds<S: Storage, A: Api, Q: Querier>(
+    deps: &mut Extern<S, A, Q>,
+    env: Env,
+    info: MessageInfo,
+    state: State,
+) -> Result<HandleResponse, ContractError> {
+    // The owner or a treasurer can distribute funds.
+    require_role(deps, &info, &state, Role::Treasurer)?;
+    // Funds can only be distributed once, after the voting period.
+    let phase = current_phase(&deps.storage, &state, &env.block)?;
+    require_phase(phase, &[RoundPhase::Tally])?;
+
+    // The contract holds the pool plus every contribution, but only the pool is
+    // available as subsidy.
+    let budget = matching_pool(&deps.storage, &state)?;
+    let votes = load_all_votes(&deps.storage)?;
+    let contributions = total_contributions(&votes);
+    let proposals = load_approved_proposals(&deps.storage)?;
+    let distributions: Vec<Distribution> = calculate_distributions(
+        &state.funding_formula,
+        votes,
+        proposals.clone(),
+        budget.clone(),
+    )?;
+    let dust = calculate_dust(&distributions, &budget)?;
+    check_conservation(&distributions, &budget, &contributions, &dust)?;
+
+    // Record exactly what is paid out so the round cannot be distributed again.
+    let mut snapshot = DistributionSnapshot {
+        height: env.block.height,
+        time: env.block.time,
+        dust: dust.clone(),
+        clawed_back: vec![],
+        leftover: vec![],
+        pending_rollover: vec![],
+        distributions: distributions.clone(),
+    };
+    let messages = settle_leftover(deps, &env, &state, &mut snapshot, &dust)?;
+    distribution_snapshot(&mut deps.storage).save(&snapshot)?;
+
+    // Recipients claim their distribution themselves, so a single bad recipient
+    // can't block the others from being paid.
+    let mut payouts: BTreeMap<u32, Claim> = BTreeMap::new();
+    for d in distributions {
+        if d.distribution_actual.amount.is_zero() {
+            continue;
+        }
+        let claim = payouts.entry(d.proposal).or_insert_with(|| Claim {
+            proposal: d.proposal,
+            recipient: d.recipient.clone(),
+            amount: vec![],
+            withdrawn: vec![],
+            status: ClaimStatus::Unclaimed,
+            // Milestone proposals are held in escrow until each stage is released.
+            milestones: proposals
+                .iter()
+                .find(|p| p.id == d.proposal)
+                .map(|p| {
+                    p.milestones
+                        .iter()
+                        .map(|m| ClaimMilestone {
+                            share: m.share,
+                            status: MilestoneStatus::Pending,
+                        })
+                        .collect()
+                })
+                .unwrap_or_default(),
+        });
+        claim.amount = add_coins(claim.amount.clone(), vec![d.distribution_actual]);
+    }
+    for (proposal_id, claim) in payouts {
+        claims(&mut deps.storage).save(&proposal_id.to_be_bytes(), &claim)?;
+    }
+
+    Ok(HandleResponse {
         messages,
+        attributes: vec![attr("action", "distribute funds")],
         data: None,
-        attributes,
+    })
+}
+
+// Leftovers are paid out straight away, as nobody else can claim them. Rollovers
+// are only recorded, so a successor round that rejects them can't stop this round
+// from being finalized.
+fn settle_leftover<S: Storage, A: Api, Q: Querier>(
+    deps: &Extern<S, A, Q>,
+    env: &Env,
+    state: &State,
+    snapshot: &mut DistributionSnapshot,
+    amount: &[Coin],
+) -> StdResult<Vec<CosmosMsg>> {
+    if let LeftoverPolicy::Rollover { .. } = state.leftover_policy {
+        let amount = amount.iter().filter(|c| !c.amount.is_zero()).cloned();
+        snapshot.pending_rollover = add_coins(snapshot.pending_rollover.clone(), amount.collect());
+        return Ok(vec![]);
+    }
+    let leftover = leftover_payments(deps, state, &state.leftover_policy, amount)?;
+    snapshot.leftover.extend(leftover.clone());
+    leftover_messages(env, &state.leftover_policy, leftover)
+}
+
+pub fn try_send_rollover<S: Storage, A: Api, Q: Querier>(
+    deps: &mut Extern<S, A, Q>,
+    env: Env,
+    info: MessageInfo,
+    state: State,
+    to_sponsors: bool,
+) -> Result<HandleResponse, ContractError> {
+    require_role(deps, &info, &state, Role::Treasurer)?;
+    let phase = current_phase(&deps.storage, &state, &env.block)?;
+    require_phase(phase, &[RoundPhase::Distributed])?;
+
+    let mut snapshot = distribution_snapshot_read(&deps.storage).load()?;
+    if snapshot.pending_rollover.is_empty() {
+        return Err(ContractError::NothingToClaim {});
+    }
+    let policy = match to_sponsors {
+        true => LeftoverPolicy::Sponsors {},
+        false => state.leftover_policy.clone(),
     };
-    Ok(r)
+    let leftover = leftover_payments(deps, &state, &policy, &snapshot.pending_rollover)?;
+    // Only what was actually paid out stops being pending.
+    let paid = leftover
+        .iter()
+        .fold(vec![], |paid, p| add_coins(paid, p.amount.clone()));
+    snapshot.pending_rollover = sub_coins(snapshot.pending_rollover, &paid);
+    snapshot.leftover.extend(leftover.clone());
+    distribution_snapshot(&mut deps.storage).save(&snapshot)?;
+
+    Ok(HandleResponse {
+        messages: leftover_messages(&env, &policy, leftover)?,
+        attributes: vec![
+            attr("action", "send rollover"),
+            attr("to_sponsors", to_sponsors),
+        ],
+        data: None,
+    })
+}
+
+fn leftover_messages(
+    env: &Env,
+    policy: &LeftoverPolicy,
+    payments: Vec<LeftoverPayment>,
+) -> StdResult<Vec<CosmosMsg>> {
+    let mut messages = vec![];
+    for payment in payments {
+        let msg = match policy {
+            LeftoverPolicy::Rollover { .. } => CosmosMsg::Wasm(WasmMsg::Execute {
+                contract_addr: payment.recipient,
+                msg: to_binary(&HandleMsg::FundPool {})?,
+                send: payment.amount,
+            }),
+            _ => CosmosMsg::Bank(BankMsg::Send {
+                from_address: env.contract.address.clone(),
+                to_address: payment.recipient,
+                amount: payment.amount,
+            }),
+        };
+        messages.push(msg);
+    }
+    Ok(messages)
+}
+
+// Splits the dust according to the leftover policy.
+fn leftover_payments<S: Storage, A: Api, Q: Querier>(
+    deps: &Extern<S, A, Q>,
+    state: &State,
+    policy: &LeftoverPolicy,
+    dust: &[Coin],
+) -> StdResult<Vec<LeftoverPayment>> {
+    let dust: Vec<Coin> = dust
+        .iter()
+        .filter(|c| !c.amount.is_zero())
+        .cloned()
+        .collect();
+    if dust.is_empty() {
+        return Ok(vec![]);
+    }
+    let recipient = match policy {
+        LeftoverPolicy::Treasury { address } => address,
+        LeftoverPolicy::Rollover { round } => round,
+        LeftoverPolicy::Sponsors {} => return sponsor_shares(deps, &state.owner, &dust),
+    };
+    Ok(vec![LeftoverPayment {
+        recipient: recipient.clone(),
+        amount: dust,
+    }])
+}
+
+// Each sponsor's share of the dust, in proportion to what they deposited in each
+// denom. What rounding leaves goes to the largest sponsor in that denom, and dust
+// in a denom no sponsor deposited goes to the owner.
+fn sponsor_shares<S: Storage, A: Api, Q: Querier>(
+    deps: &Extern<S, A, Q>,
+    owner: &CanonicalAddr,
+    dust: &[Coin],
+) -> StdResult<Vec<LeftoverPayment>> {
+    let deposits: Vec<(CanonicalAddr, Vec<Coin>)> = sponsors_read(&deps.storage)
+        .range(None, None, Order::Ascending)
+        .map(|item| item.map(|(sponsor, amount)| (CanonicalAddr::from(sponsor), amount)))
+        .collect::<StdResult<_>>()?;
+    let pool = pool_read(&deps.storage).may_load()?.unwrap_or_default();
+
+    let mut shares: Vec<Vec<Coin>> = vec![vec![]; deposits.len()];
+    let mut unsponsored: Vec<Coin> = vec![];
+    for c in dust {
+        let total = amount_of(&pool, &c.denom);
+        let mut remaining = c.amount;
+        let mut largest: Option<(usize, Uint128)> = None;
+        for (i, (_, deposit)) in deposits.iter().enumerate() {
+            let deposited = amount_of(deposit, &c.denom);
+            if deposited.is_zero() {
+                continue;
+            }
+            let share = c.amount.multiply_ratio(deposited, total);
+            remaining = (remaining - share)?;
+            shares[i] = add_coins(shares[i].clone(), vec![coin(share.u128(), &c.denom)]);
+            let is_largest = match largest {
+                None => true,
+                Some((_, amount)) => deposited > amount,
+            };
+            if is_largest {
+                largest = Some((i, deposited));
+            }
+        }
+        match largest {
+            Some((i, _)) => {
+                shares[i] = add_coins(shares[i].clone(), vec![coin(remaining.u128(), &c.denom)])
+            }
+            None => unsponsored.push(c.clone()),
+        }
+    }
+
+    let mut payments = vec![];
+    for ((sponsor, _), amount) in deposits.iter().zip(shares) {
+        let amount: Vec<Coin> = amount.into_iter().filter(|c| !c.amount.is_zero()).collect();
+        if !amount.is_empty() {
+            payments.push(LeftoverPayment {
+                recipient: deps.api.human_address(sponsor)?,
+                amount,
+            });
+        }
+    }
+    if !unsponsored.is_empty() {
+        payments.push(LeftoverPayment {
+            recipient: deps.api.human_address(owner)?,
+            amount: unsponsored,
+        });
+    }
+    Ok(payments)
+}
+
+pub fn try_claim<S: Storage, A: Api, Q: Querier>(
+    deps: &mut Extern<S, A, Q>,
+    env: Env,
+    info: MessageInfo,
+    state: State,
+    proposal_id: u32,
+) -> Result<HandleResponse, ContractError> {
+    if state.vesting.is_some() {
+        return Err(ContractError::VestingEnabled {});
+    }
+    let mut claim = load_open_claim(deps, &env, &info, &state, proposal_id)?;
+
+    let payout = sub_coins(released_amount(&claim), &claim.withdrawn);
+    if payout.is_empty() {
+        return Err(ContractError::NothingToClaim {});
+    }
+    claim.withdrawn = add_coins(claim.withdrawn, payout.clone());
+    if sub_coins(entitled_amount(&claim), &claim.withdrawn).is_empty() {
+        claim.status = ClaimStatus::Claimed;
+    }
+    claims(&mut deps.storage).save(&proposal_id.to_be_bytes(), &claim)?;
+    Ok(HandleResponse {
+        messages: vec![CosmosMsg::Bank(BankMsg::Send {
+            from_address: env.contract.address,
+            to_address: info.sender,
+            amount: payout,
+        })],
+        attributes: vec![attr("action", "claim"), attr("proposal_id", proposal_id)],
+        data: None,
+    })
+}
+
+pub fn try_withdraw_vested<S: Storage, A: Api, Q: Querier>(
+    deps: &mut Extern<S, A, Q>,
+    env: Env,
+    info: MessageInfo,
+    state: State,
+    proposal_id: u32,
+) -> Result<HandleResponse, ContractError> {
+    let schedule = match state.vesting {
+        Some(schedule) => schedule,
+        None => return Err(ContractError::VestingDisabled {}),
+    };
+    let mut claim = load_open_claim(deps, &env, &info, &state, proposal_id)?;
+
+    let vested = schedule.vested(&released_amount(&claim), &env.block);
+    let payout = sub_coins(vested, &claim.withdrawn);
+    if payout.is_empty() {
+        return Err(ContractError::NothingToClaim {});
+    }
+    claim.withdrawn = add_coins(claim.withdrawn, payout.clone());
+    if sub_coins(entitled_amount(&claim), &claim.withdrawn).is_empty() {
+        claim.status = ClaimStatus::Claimed;
+    }
+    claims(&mut deps.storage).save(&proposal_id.to_be_bytes(), &claim)?;
+    Ok(HandleResponse {
+        messages: vec![CosmosMsg::Bank(BankMsg::Send {
+            from_address: env.contract.address,
+            to_address: info.sender,
+            amount: payout,
+        })],
+        attributes: vec![
+            attr("action", "withdraw vested"),
+            attr("proposal_id", proposal_id),
+        ],
+        data: None,
+    })
+}
+
+// Loads a claim the sender is allowed to be paid from right now.
+fn load_open_claim<S: Storage, A: Api, Q: Querier>(
+    deps: &Extern<S, A, Q>,
+    env: &Env,
+    info: &MessageInfo,
+    state: &State,
+    proposal_id: u32,
+) -> Result<Claim, ContractError> {
+    let phase = current_phase(&deps.storage, state, &env.block)?;
+    require_phase(phase, &[RoundPhase::Distributed])?;
+
+    let claim = match claims_read(&deps.storage).may_load(&proposal_id.to_be_bytes())? {
+        Some(claim) => claim,
+        None => return Err(ContractError::NothingToClaim {}),
+    };
+    // Only the proposal's recipient can claim.
+    if deps.api.canonical_address(&info.sender)? != claim.recipient {
+        return Err(ContractError::Unauthorized {
+            list_type: "recipient".to_string(),
+        });
+    }
+    match claim.status {
+        ClaimStatus::Unclaimed => {}
+        ClaimStatus::Claimed => return Err(ContractError::AlreadyClaimed {}),
+        ClaimStatus::Returned => return Err(ContractError::ClaimExpired {}),
+    }
+    if let Some(deadline) = state.claim_deadline {
+        if deadline.is_expired(&env.block) {
+            return Err(ContractError::ClaimExpired {});
+        }
+    }
+    Ok(claim)
+}
+
+pub fn try_return_unclaimed<S: Storage, A: Api, Q: Querier>(
+    deps: &mut Extern<S, A, Q>,
+    env: Env,
+    info: MessageInfo,
+    state: State,
+) -> Result<HandleResponse, ContractError> {
+    // Only the contract owner receives unclaimed funds.
+    require_owner(deps, &info, &state)?;
+    let phase = current_phase(&deps.storage, &state, &env.block)?;
+    require_phase(phase, &[RoundPhase::Distributed])?;
+    match state.claim_deadline {
+        Some(deadline) if deadline.is_expired(&env.block) => {}
+        _ => return Err(ContractError::ClaimPeriodOpen {}),
+    }
+
+    let all_claims: Vec<Claim> = claims_read(&deps.storage)
+        .range(None, None, Order::Ascending)
+        .map(|item| item.map(|(_, claim)| claim))
+        .collect::<StdResult<_>>()?;
+    let mut amount: Vec<Coin> = vec![];
+    for mut claim in all_claims
+        .into_iter()
+        .filter(|claim| claim.status == ClaimStatus::Unclaimed)
+    {
+        amount = add_coins(amount, sub_coins(entitled_amount(&claim), &claim.withdrawn));
+        claim.status = ClaimStatus::Returned;
+        claims(&mut deps.storage).save(&claim.proposal.to_be_bytes(), &claim)?;
+    }
+    if amount.is_empty() {
+        return Err(ContractError::NothingToClaim {});
+    }
+
+    Ok(HandleResponse {
+        messages: vec![CosmosMsg::Bank(BankMsg::Send {
+            from_address: env.contract.address,
+            to_address: info.sender,
+            amount,
+        })],
+        attributes: vec![attr("action", "return unclaimed")],
+        data: None,
+    })
+}
+
+pub fn try_release_milestone<S: Storage, A: Api, Q: Querier>(
+    deps: &mut Extern<S, A, Q>,
+    env: Env,
+    info: MessageInfo,
+    state: State,
+    proposal_id: u32,
+    milestone: u32,
+) -> Result<HandleResponse, ContractError> {
+    let mut claim = load_escrowed_claim(deps, &env, &info, &state, proposal_id)?;
+    let stage = match claim.milestones.get_mut(milestone as usize) {
+        Some(stage) => stage,
+        None => {
+            return Err(ContractError::InvalidMilestones {
+                reason: format!("proposal has no milestone {}", milestone),
+            })
+        }
+    };
+    if stage.status != MilestoneStatus::Pending {
+        return Err(ContractError::MilestoneNotPending { milestone });
+    }
+    stage.status = MilestoneStatus::Released;
+    claims(&mut deps.storage).save(&proposal_id.to_be_bytes(), &claim)?;
+
+    Ok(HandleResponse {
+        messages: vec![],
+        attributes: vec![
+            attr("action", "release milestone"),
+            attr("proposal_id", proposal_id),
+            attr("milestone", milestone),
+        ],
+        data: None,
+    })
+}
+
+// Takes back every milestone that hasn't been released yet, for an abandoned project.
+pub fn try_claw_back<S: Storage, A: Api, Q: Querier>(
+    deps: &mut Extern<S, A, Q>,
+    env: Env,
+    info: MessageInfo,
+    state: State,
+    proposal_id: u32,
+) -> Result<HandleResponse, ContractError> {
+    let mut claim = load_escrowed_claim(deps, &env, &info, &state, proposal_id)?;
+    if !claim
+        .milestones
+        .iter()
+        .any(|stage| stage.status == MilestoneStatus::Pending)
+    {
+        return Err(ContractError::NothingToClaim {});
+    }
+    for stage in claim.milestones.iter_mut() {
+        if stage.status == MilestoneStatus::Pending {
+            stage.status = MilestoneStatus::ClawedBack;
+        }
+    }
+    let amount = sub_coins(claim.amount.clone(), &entitled_amount(&claim));
+    if sub_coins(entitled_amount(&claim), &claim.withdrawn).is_empty() {
+        claim.status = ClaimStatus::Claimed;
+    }
+    claims(&mut deps.storage).save(&proposal_id.to_be_bytes(), &claim)?;
+
+    // Unreleased funds are handled like the pool's dust, under the leftover policy.
+    let mut snapshot = distribution_snapshot_read(&deps.storage).load()?;
+    snapshot.clawed_back = add_coins(snapshot.clawed_back, amount.clone());
+    let messages = settle_leftover(deps, &env, &state, &mut snapshot, &amount)?;
+    distribution_snapshot(&mut deps.storage).save(&snapshot)?;
+
+    Ok(HandleResponse {
+        messages,
+        attributes: vec![
+            attr("action", "claw back"),
+            attr("proposal_id", proposal_id),
+        ],
+        data: None,
+    })
+}
+
+// Loads a claim whose milestones the sender, the owner or a reviewer, can manage.
+fn load_escrowed_claim<S: Storage, A: Api, Q: Querier>(
+    deps: &Extern<S, A, Q>,
+    env: &Env,
+    info: &MessageInfo,
+    state: &State,
+    proposal_id: u32,
+) -> Result<Claim, ContractError> {
+    require_role(deps, info, state, Role::Reviewer)?;
+    let phase = current_phase(&deps.storage, state, &env.block)?;
+    require_phase(phase, &[RoundPhase::Distributed])?;
+
+    match claims_read(&deps.storage).may_load(&proposal_id.to_be_bytes())? {
+        Some(claim) if claim.status != ClaimStatus::Returned => Ok(claim),
+        _ => Err(ContractError::NothingToClaim {}),
+    }
+}
+
+// Part of the claim that is out of escrow. Claims without milestones are never escrowed.
+fn released_amount(claim: &Claim) -> Vec<Coin> {
+    if claim.milestones.is_empty() {
+        return claim.amount.clone();
+    }
+    let released = claim
+        .milestones
+        .iter()
+        .filter(|stage| stage.status == MilestoneStatus::Released)
+        .fold(Decimal::zero(), |total, stage| total + stage.share);
+    share_of(&claim.amount, released)
+}
+
+// Part of the claim the recipient can still be paid, once clawed back milestones
+// are taken out.
+fn entitled_amount(claim: &Claim) -> Vec<Coin> {
+    if claim
+        .milestones
+        .iter()
+        .any(|stage| stage.status == MilestoneStatus::ClawedBack)
+    {
+        return released_amount(claim);
+    }
+    claim.amount.clone()
+}
+
+fn share_of(coins: &[Coin], share: Decimal) -> Vec<Coin> {
+    coins
+        .iter()
+        .map(|c| Coin {
+            denom: c.denom.clone(),
+            amount: c.amount * share,
+        })
+        .filter(|c| !c.amount.is_zero())
+        .collect()
+}
+
+pub fn try_cancel_round<S: Storage, A: Api, Q: Querier>(
+    deps: &mut Extern<S, A, Q>,
+    env: Env,
+    info: MessageInfo,
+    state: State,
+) -> Result<HandleResponse, ContractError> {
+    // Only the contract owner can cancel the round.
+    require_owner(deps, &info, &state)?;
+    // A round can be cancelled at any point until funds are distributed.
+    let phase = current_phase(&deps.storage, &state, &env.block)?;
+    require_phase(
+        phase,
+        &[
+            RoundPhase::Setup,
+            RoundPhase::Proposals,
+            RoundPhase::Review,
+            RoundPhase::Voting,
+            RoundPhase::Tally,
+        ],
+    )?;
+
+    config(&mut deps.storage).update(|mut state| -> Result<State, ContractError> {
+        state.cancelled = true;
+        Ok(state)
+    })?;
+    Ok(HandleResponse {
+        messages: vec![],
+        attributes: vec![attr("action", "cancel round")],
+        data: None,
+    })
+}
+
+pub fn try_claim_refund<S: Storage, A: Api, Q: Querier>(
+    deps: &mut Extern<S, A, Q>,
+    env: Env,
+    info: MessageInfo,
+    state: State,
+) -> Result<HandleResponse, ContractError> {
+    let phase = current_phase(&deps.storage, &state, &env.block)?;
+    require_phase(phase, &[RoundPhase::Cancelled])?;
+
+    let claimant = deps.api.canonical_address(&info.sender)?;
+    if refunds_read(&deps.storage)
+        .may_load(claimant.as_slice())?
+        .is_some()
+    {
+        return Err(ContractError::AlreadyRefunded {});
+    }
+
+    // Everything the sender voted with, plus anything they added to the pool.
+    let mut refund: Vec<Coin> = vec![];
+    let proposal_ids: Vec<u32> = voter_index_read(&deps.storage, &claimant)
+        .range(None, None, Order::Ascending)
+        .map(|item| item.map(|(_, proposal_id)| proposal_id))
+        .collect::<StdResult<_>>()?;
+    for proposal_id in proposal_ids {
+        let vote = votes_read(&deps.storage, proposal_id).load(claimant.as_slice())?;
+        refund = add_coins(refund, vote.amount);
+    }
+    if let Some(deposit) = sponsors_read(&deps.storage).may_load(claimant.as_slice())? {
+        refund = add_coins(refund, deposit);
+    }
+    refund.retain(|c| !c.amount.is_zero());
+    if refund.is_empty() {
+        return Err(ContractError::NothingToRefund {});
+    }
+
+    refunds(&mut deps.storage).save(claimant.as_slice(), &refund)?;
+    Ok(HandleResponse {
+        messages: vec![CosmosMsg::Bank(BankMsg::Send {
+            from_address: env.contract.address,
+            to_address: info.sender,
+            amount: refund,
+        })],
+        attributes: vec![attr("action", "claim refund")],
+        data: None,
+    })
+}
+
+// Collapses repeat votes by a voter on a proposal into a single vote, keeping
+// each denom separate.
+pub fn get_normalized_votes(votes: &[Vote]) -> Vec<Vote> {
+    let mut unique: BTreeMap<String, Vote> = BTreeMap::new();
+    for vote in votes {
+        let tag = format!("{}--{}", vote.voter, vote.proposal);
+        match unique.get_mut(&tag) {
+            Some(existing) => {
+                existing.amount = add_coins(existing.amount.clone(), vote.amount.clone())
+            }
+            None => {
+                unique.insert(tag, vote.clone());
+            }
+        }
+    }
+    unique.values().cloned().collect()
+}
+
+// Everything voters have sent, summed per denom.
+pub fn total_contributions(votes: &[Vote]) -> Vec<Coin> {
+    votes
+        .iter()
+        .fold(vec![], |total, v| add_coins(total, v.amount.clone()))
+}
+
+// Every coin held for the round has to be accounted for: in each denom, the
+// distributions plus the dust must equal the pool plus the contributions.
+pub fn check_conservation(
+    distributions: &[Distribution],
+    budget: &[Coin],
+    contributions: &[Coin],
+    dust: &[Coin],
+) -> Result<(), ContractError> {
+    let paid = distributions.iter().fold(vec![], |total, d| {
+        add_coins(total, vec![d.distribution_actual.clone()])
+    });
+    let held = add_coins(budget.to_vec(), contributions.to_vec());
+    let accounted = add_coins(paid, dust.to_vec());
+    for c in held.iter().chain(accounted.iter()) {
+        if amount_of(&held, &c.denom) != amount_of(&accounted, &c.denom) {
+            return Err(ContractError::ConservationViolated {
+                denom: c.denom.clone(),
+            });
+        }
+    }
+    Ok(())
+}
+
+fn amount_of(coins: &[Coin], denom: &str) -> Uint128 {
+    coins
+        .iter()
+        .filter(|c| c.denom == denom)
+        .fold(Uint128::zero(), |total, c| total + c.amount)
+}
+
+// The votes made in `denom`, with every other denom dropped.
+pub fn votes_in_denom(votes: &[Vote], denom: &str) -> Vec<Vote> {
+    votes
+        .iter()
+        .filter_map(|v| {
+            v.amount
+                .iter()
+                .find(|c| c.denom == denom && !c.amount.is_zero())
+                .map(|c| Vote {
+                    voter: v.voter.clone(),
+                    proposal: v.proposal,
+                    amount: vec![c.clone()],
+                })
+        })
+        .collect()
+}
+
+// The part of the contract balance in denoms the round accepts. Each of these is
+// a separate matching pool.
+pub fn accepted_balance(balance: Vec<Coin>, accepted_denoms: &[String]) -> Vec<Coin> {
+    balance
+        .into_iter()
+        .filter(|c| accepted_denoms.contains(&c.denom) && !c.amount.is_zero())
+        .collect()
+}
+
+// Each coin in the budget is matched separately against the votes in its denom,
+// so there is one distribution per proposal for every denom in the budget. The
+// budget is the matching pool alone: contributions are paid back on top of their
+// subsidy, so they must not be part of it.
+pub fn calculate_distributions(
+    funding_formula: &FundingFormula,
+    votes: Vec<Vote>,
+    proposals: Vec<Proposal>,
+    budget_contstraint: Vec<Coin>,
+) -> Result<Vec<Distribution>, ContractError> {
+    // Without a pool there is still something to do as long as there are
+    // contributions to pay back.
+    if budget_contstraint.is_empty() && votes.is_empty() {
+        return Err(ContractError::EmptyBudget {});
+    }
+
+    // Collapse multiple votes all votes by a single voter for a single proposal
+    let unique_votes = get_normalized_votes(&votes);
+
+    // Votes in a denom without a pool are still paid back, just without matching.
+    let mut budgets = budget_contstraint;
+    for c in total_contributions(&unique_votes) {
+        if !budgets.iter().any(|b| b.denom == c.denom) {
+            budgets.push(coin(0, &c.denom));
+        }
+    }
+
+    let mut distributions = vec![];
+    for budget in budgets {
+        let denom = budget.denom;
+        let formula = funding_formula.formula(&denom);
+        let denom_votes = votes_in_denom(&unique_votes, &denom);
+        let overflow = || ContractError::MatchingOverflow {
+            denom: denom.clone(),
+        };
+        let subsidies_ideal = formula
+            .ideal_subsidies(&denom_votes, &proposals)
+            .ok_or_else(overflow)?;
+        let subsidies_actual = formula
+            .actual_subsidies(&subsidies_ideal, budget.amount.u128())
+            .ok_or_else(overflow)?;
+
+        // Proposals without any subsidy, e.g. when every proposal has a single voter,
+        // receive their contributions back and no matching.
+        for (p, (subsidy_ideal, subsidy_actual)) in proposals
+            .iter()
+            .zip(subsidies_ideal.into_iter().zip(subsidies_actual))
+        {
+            let votes = proposal_votes(&denom_votes, p.id);
+            let total_votes: u128 = votes.iter().sum();
+            let subsidy_ideal = subsidy_ideal.checked_floor().ok_or_else(overflow)?;
+            let distribution_ideal = total_votes
+                .checked_add(subsidy_ideal)
+                .ok_or_else(overflow)?;
+            distributions.push(Distribution {
+                proposal: p.id,
+                recipient: p.recipient.clone(),
+                votes: votes.iter().map(|v| coin(*v, &denom)).collect(),
+                distribution_ideal: coin(distribution_ideal, &denom),
+                subsidy_ideal: coin(subsidy_ideal, &denom),
+                distribution_actual: coin(total_votes + subsidy_actual, &denom),
+                subsidy_actual: coin(subsidy_actual, &denom),
+            });
+        }
+    }
+    Ok(distributions)
+}
+
+// Part of each budget denom left over after every subsidy is rounded down.
+pub fn calculate_dust(
+    distributions: &[Distribution],
+    budget_contstraint: &[Coin],
+) -> Result<Vec<Coin>, ContractError> {
+    Ok(budget_contstraint
+        .iter()
+        .map(|budget| {
+            let subsidies: u128 = distributions
+                .iter()
+                .filter(|d| d.subsidy_actual.denom == budget.denom)
+                .map(|d| d.subsidy_actual.amount.u128())
+                .sum();
+            coin(budget.amount.u128() - subsidies, &budget.denom)
+        })
+        .collect())
 }
 
 // TODO: Add query Proposal + Votes by Proposal ID.
 pub fn query<S: Storage, A: Api, Q: Querier>(
     deps: &Extern<S, A, Q>,
-    _env: Env,
+    env: Env,
     msg: QueryMsg,
 ) -> StdResult<Binary> {
     match msg {
         QueryMsg::GetState {} => to_binary(&query_state(deps)?),
-        QueryMsg::ProposalList {} => to_binary(&query_proposal_list(deps)?),
+        QueryMsg::ProposalList { start_after, limit } => {
+            to_binary(&query_proposal_list(deps, start_after, limit)?)
+        }
         QueryMsg::ProposalState { proposal_id } => query_proposal_state(deps, proposal_id),
+        QueryMsg::VotesByProposal {
+            proposal_id,
+            start_after,
+            limit,
+        } => to_binary(&query_votes_by_proposal(
+            deps,
+            proposal_id,
+            start_after,
+            limit,
+        )?),
+        QueryMsg::VotesByVoter {
+            voter,
+            start_after,
+            limit,
+        } => to_binary(&query_votes_by_voter(deps, voter, start_after, limit)?),
+        QueryMsg::FinalDistributions {} => to_binary(&query_final_distributions(deps)?),
+        QueryMsg::Distributions { hypothetical_vote } => {
+            to_binary(&query_distributions(deps, hypothetical_vote)?)
+        }
+        QueryMsg::Phase {} => to_binary(&query_phase(deps, env)?),
+        QueryMsg::Claims { start_after, limit } => {
+            to_binary(&query_claims(deps, start_after, limit)?)
+        }
+        QueryMsg::Vesting { proposal_id } => to_binary(&query_vesting(deps, env, proposal_id)?),
+        QueryMsg::Roles {} => to_binary(&query_roles(deps)?),
+        QueryMsg::Sponsors { start_after, limit } => {
+            to_binary(&query_sponsors(deps, start_after, limit)?)
+        }
+    }
+}
+
+fn page_limit(limit: Option<u32>) -> usize {
+    limit.unwrap_or(DEFAULT_LIMIT).min(MAX_LIMIT) as usize
+}
+
+// Pages are loaded with one extra item to tell whether another page follows. Drops
+// it and returns the last item kept if so.
+fn split_page<T>(items: &mut Vec<T>, limit: usize) -> Option<&T> {
+    if items.len() <= limit {
+        return None;
     }
+    items.truncate(limit);
+    items.last()
+}
+
+// Returns the smallest key strictly greater than `key`, for exclusive range starts.
+fn exclusive_start(key: &[u8]) -> Vec<u8> {
+    let mut start = key.to_vec();
+    start.push(0);
+    start
 }
 
 fn query_state<S: Storage, A: Api, Q: Querier>(deps: &Extern<S, A, Q>) -> StdResult<StateResponse> {
@@ -670,49 +1770,285 @@ fn query_state<S: Storage, A: Api, Q: Querier>(deps: &Extern<S, A, Q>) -> StdRes
         .proposer_whitelist
         .iter()
         .map(|x| deps.api.human_address(x))
-        .filter_map(Result::ok)
-        .collect();
+        .collect::<StdResult<_>>()?;
     let voter_whitelist = state
         .voter_whitelist
         .iter()
         .map(|x| deps.api.human_address(x))
-        .filter_map(Result::ok)
-        .collect();
+        .collect::<StdResult<_>>()?;
+    let reviewers = state
+        .reviewers
+        .iter()
+        .map(|x| deps.api.human_address(x))
+        .collect::<StdResult<_>>()?;
     Ok(StateResponse {
         name: state.name,
         proposer_whitelist,
         voter_whitelist,
+        open_proposals: state.open_proposals,
+        open_voting: state.open_voting,
+        voter_merkle_root: state.voter_merkle_root,
+        group: state.group,
         proposal_period_start: state.proposal_period_start,
         proposal_period_end: state.proposal_period_end,
         voting_period_start: state.voting_period_start,
         voting_period_end: state.voting_period_end,
+        funding_formula: state.funding_formula,
+        accepted_denoms: state.accepted_denoms,
+        min_contribution: state.min_contribution,
+        claim_deadline: state.claim_deadline,
+        vesting: state.vesting,
+        reviewers,
+        require_approval: state.require_approval,
+        leftover_policy: state.leftover_policy,
     })
 }
 
 fn query_proposal_list<S: Storage, A: Api, Q: Querier>(
     deps: &Extern<S, A, Q>,
+    start_after: Option<u32>,
+    limit: Option<u32>,
 ) -> StdResult<ProposalListResponse> {
-    let state = config_read(&deps.storage).load()?;
-    let proposals = state.proposals;
-    Ok(ProposalListResponse { proposals })
+    let limit = page_limit(limit);
+    let start = start_after.map(|id| exclusive_start(&id.to_be_bytes()));
+    let mut proposals: Vec<Proposal> = proposals_read(&deps.storage)
+        .range(start.as_deref(), None, Order::Ascending)
+        .take(limit + 1)
+        .map(|item| item.map(|(_, proposal)| proposal))
+        .collect::<StdResult<_>>()?;
+    let next_start_after = split_page(&mut proposals, limit).map(|p| p.id);
+    Ok(ProposalListResponse {
+        proposals,
+        next_start_after,
+    })
+}
+
+fn query_votes_by_proposal<S: Storage, A: Api, Q: Querier>(
+    deps: &Extern<S, A, Q>,
+    proposal_id: u32,
+    start_after: Option<HumanAddr>,
+    limit: Option<u32>,
+) -> StdResult<VotesByProposalResponse> {
+    let limit = page_limit(limit);
+    let start = match start_after {
+        Some(voter) => Some(exclusive_start(
+            deps.api.canonical_address(&voter)?.as_slice(),
+        )),
+        None => None,
+    };
+    let mut votes: Vec<Vote> = votes_read(&deps.storage, proposal_id)
+        .range(start.as_deref(), None, Order::Ascending)
+        .take(limit + 1)
+        .map(|item| item.map(|(_, vote)| vote))
+        .collect::<StdResult<_>>()?;
+    let next_start_after = match split_page(&mut votes, limit) {
+        Some(vote) => Some(deps.api.human_address(&vote.voter)?),
+        None => None,
+    };
+    Ok(VotesByProposalResponse {
+        votes,
+        next_start_after,
+    })
+}
+
+fn query_votes_by_voter<S: Storage, A: Api, Q: Querier>(
+    deps: &Extern<S, A, Q>,
+    voter: HumanAddr,
+    start_after: Option<u32>,
+    limit: Option<u32>,
+) -> StdResult<VotesByVoterResponse> {
+    let limit = page_limit(limit);
+    let voter = deps.api.canonical_address(&voter)?;
+    let start = start_after.map(|id| exclusive_start(&id.to_be_bytes()));
+    let mut votes: Vec<Vote> = voter_index_read(&deps.storage, &voter)
+        .range(start.as_deref(), None, Order::Ascending)
+        .take(limit + 1)
+        .map(|item| {
+            let (_, proposal_id) = item?;
+            votes_read(&deps.storage, proposal_id).load(voter.as_slice())
+        })
+        .collect::<StdResult<_>>()?;
+    let next_start_after = split_page(&mut votes, limit).map(|v| v.proposal);
+    Ok(VotesByVoterResponse {
+        votes,
+        next_start_after,
+    })
 }
 
 fn query_proposal_state<S: Storage, A: Api, Q: Querier>(
     deps: &Extern<S, A, Q>,
     proposal_id: u32,
 ) -> StdResult<Binary> {
-    let state = config_read(&deps.storage).load()?;
-    let proposal = match state.proposals.into_iter().find(|p| p.id == proposal_id) {
-        Some(proposal) => Some(proposal),
+    let proposal = match proposals_read(&deps.storage).may_load(&proposal_id.to_be_bytes())? {
+        Some(proposal) => proposal,
         None => return Err(StdError::generic_err("Proposal does not exist")),
-    }
-    .unwrap();
+    };
 
-    let votes: Vec<Vote> = state
-        .votes
-        .into_iter()
-        .filter(|v| v.proposal == proposal_id)
-        .collect();
-    let resp = ProposalStateResponse { proposal, votes };
+    let resp = ProposalStateResponse { proposal };
     to_binary(&resp)
 }
+
+fn query_final_distributions<S: Storage, A: Api, Q: Querier>(
+    deps: &Extern<S, A, Q>,
+) -> StdResult<FinalDistributionsResponse> {
+    match distribution_snapshot_read(&deps.storage).may_load()? {
+        Some(snapshot) => Ok(FinalDistributionsResponse { snapshot }),
+        None => Err(StdError::generic_err("Funds have not been distributed")),
+    }
+}
+
+fn query_claims<S: Storage, A: Api, Q: Querier>(
+    deps: &Extern<S, A, Q>,
+    start_after: Option<u32>,
+    limit: Option<u32>,
+) -> StdResult<ClaimsResponse> {
+    let state = config_read(&deps.storage).load()?;
+    let limit = page_limit(limit);
+    let start = start_after.map(|id| exclusive_start(&id.to_be_bytes()));
+    let mut claims: Vec<Claim> = claims_read(&deps.storage)
+        .range(start.as_deref(), None, Order::Ascending)
+        .take(limit + 1)
+        .map(|item| item.map(|(_, claim)| claim))
+        .collect::<StdResult<_>>()?;
+    let next_start_after = split_page(&mut claims, limit).map(|c| c.proposal);
+    Ok(ClaimsResponse {
+        claims,
+        deadline: state.claim_deadline,
+        next_start_after,
+    })
+}
+
+fn query_roles<S: Storage, A: Api, Q: Querier>(deps: &Extern<S, A, Q>) -> StdResult<RolesResponse> {
+    let state = config_read(&deps.storage).load()?;
+    let human_list = |list: &[CanonicalAddr]| -> StdResult<Vec<HumanAddr>> {
+        list.iter().map(|x| deps.api.human_address(x)).collect()
+    };
+    Ok(RolesResponse {
+        owner: deps.api.human_address(&state.owner)?,
+        pending_owner: match &state.pending_owner {
+            Some(owner) => Some(deps.api.human_address(owner)?),
+            None => None,
+        },
+        operators: human_list(&state.operators)?,
+        treasurers: human_list(&state.treasurers)?,
+        reviewers: human_list(&state.reviewers)?,
+    })
+}
+
+fn query_sponsors<S: Storage, A: Api, Q: Querier>(
+    deps: &Extern<S, A, Q>,
+    start_after: Option<HumanAddr>,
+    limit: Option<u32>,
+) -> StdResult<SponsorsResponse> {
+    let limit = page_limit(limit);
+    let start = match start_after {
+        Some(sponsor) => Some(exclusive_start(
+            deps.api.canonical_address(&sponsor)?.as_slice(),
+        )),
+        None => None,
+    };
+    let mut sponsors = sponsors_read(&deps.storage)
+        .range(start.as_deref(), None, Order::Ascending)
+        .take(limit + 1)
+        .map(|item| {
+            let (sponsor, amount) = item?;
+            Ok(SponsorDeposit {
+                sponsor: deps.api.human_address(&CanonicalAddr::from(sponsor))?,
+                amount,
+            })
+        })
+        .collect::<StdResult<Vec<_>>>()?;
+    let next_start_after = split_page(&mut sponsors, limit).map(|d| d.sponsor.clone());
+    Ok(SponsorsResponse {
+        sponsors,
+        total: pool_read(&deps.storage).may_load()?.unwrap_or_default(),
+        next_start_after,
+    })
+}
+
+fn query_vesting<S: Storage, A: Api, Q: Querier>(
+    deps: &Extern<S, A, Q>,
+    env: Env,
+    proposal_id: u32,
+) -> StdResult<VestingResponse> {
+    let state = config_read(&deps.storage).load()?;
+    let schedule = match state.vesting {
+        Some(schedule) => schedule,
+        None => return Err(StdError::generic_err("Distributions do not vest")),
+    };
+    let claim = match claims_read(&deps.storage).may_load(&proposal_id.to_be_bytes())? {
+        Some(claim) => claim,
+        None => return Err(StdError::generic_err("Nothing to claim")),
+    };
+    Ok(VestingResponse {
+        proposal_id,
+        schedule,
+        vested: schedule.vested(&released_amount(&claim), &env.block),
+        total: claim.amount,
+        withdrawn: claim.withdrawn,
+    })
+}
+
+fn query_phase<S: Storage, A: Api, Q: Querier>(
+    deps: &Extern<S, A, Q>,
+    env: Env,
+) -> StdResult<PhaseResponse> {
+    let state = config_read(&deps.storage).load()?;
+    let phase = current_phase(&deps.storage, &state, &env.block)?;
+    // Nothing is scheduled once the round is over.
+    let deadlines = match phase {
+        RoundPhase::Distributed | RoundPhase::Cancelled => vec![],
+        _ => upcoming_deadlines(&state, &env.block),
+    };
+    Ok(PhaseResponse {
+        phase,
+        height: env.block.height,
+        time: env.block.time,
+        deadlines,
+    })
+}
+
+fn query_distributions<S: Storage, A: Api, Q: Querier>(
+    deps: &Extern<S, A, Q>,
+    hypothetical_vote: Option<HypotheticalVote>,
+) -> StdResult<CheckDistributionsResponse> {
+    let mut votes = load_all_votes(&deps.storage)?;
+    let state = config_read(&deps.storage).load()?;
+    let budget = matching_pool(&deps.storage, &state)?;
+
+    // A hypothetical vote is counted just as if it had been sent with CreateVote.
+    // Contributions aren't part of the matching pool.
+    if let Some(vote) = hypothetical_vote {
+        match proposals_read(&deps.storage).may_load(&vote.proposal_id.to_be_bytes())? {
+            Some(proposal) if !proposal.approved => {
+                let err = ContractError::ProposalNotApproved {
+                    id: vote.proposal_id,
+                };
+                return Err(StdError::generic_err(err.to_string()));
+            }
+            Some(_) => {}
+            None => return Err(StdError::generic_err("Proposal does not exist")),
+        }
+        validate_contribution(&state, std::slice::from_ref(&vote.amount))
+            .map_err(|err| StdError::generic_err(err.to_string()))?;
+        votes.push(Vote {
+            voter: deps.api.canonical_address(&vote.voter)?,
+            proposal: vote.proposal_id,
+            amount: vec![vote.amount],
+        });
+    }
+
+    let distributions = calculate_distributions(
+        &state.funding_formula,
+        votes,
+        load_approved_proposals(&deps.storage)?,
+        budget.clone(),
+    )
+    .map_err(|err| StdError::generic_err(err.to_string()))?;
+    let dust = calculate_dust(&distributions, &budget)
+        .map_err(|err| StdError::generic_err(err.to_string()))?;
+    Ok(CheckDistributionsResponse {
+        dust,
+        distributions,
+    })
+}
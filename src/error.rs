@@ -1,4 +1,4 @@
-use cosmwasm_std::StdError;
+use cosmwasm_std::{StdError, Uint128};
 use thiserror::Error;
 
 #[derive(Error, Debug)]
@@ -9,11 +9,98 @@ pub enum ContractError {
     #[error("Unauthorized: Sender address not in {list_type:?} list")]
     Unauthorized { list_type: String },
 
+    #[error("Invalid address {address:?} in {list:?}")]
+    InvalidAddress { list: String, address: String },
+
+    #[error("Voter merkle root must be a hex encoded SHA-256 hash")]
+    InvalidMerkleRoot {},
+
+    #[error("Merkle proof does not show the sender is an eligible voter")]
+    InvalidMerkleProof {},
+
+    #[error("Voter merkle root can't be used with open voting")]
+    MerkleRootWithOpenVoting {},
+
+    #[error("Contributions are capped at {cap}{denom} for this voter")]
+    AboveCap { cap: Uint128, denom: String },
+
     #[error("Invalid {period_type:?} period")]
     InvalidPeriod { period_type: String },
 
+    #[error("Invalid schedule: {reason}")]
+    InvalidSchedule { reason: String },
+
     #[error("Invalid proposal id: {id:?}")]
     InvalidProposal { id: u32 },
+
+    #[error("Proposal {id:?} has not been approved")]
+    ProposalNotApproved { id: u32 },
+
+    #[error("Proposal {id:?} is already approved")]
+    AlreadyApproved { id: u32 },
+
+    #[error("Funds have already been distributed")]
+    AlreadyDistributed {},
+
+    #[error("Invalid funding formula: {reason}")]
+    InvalidFundingFormula { reason: String },
+
+    #[error("Round has been cancelled")]
+    RoundCancelled {},
+
+    #[error("Nothing to refund")]
+    NothingToRefund {},
+
+    #[error("Refund has already been claimed")]
+    AlreadyRefunded {},
+
+    #[error("Nothing to claim")]
+    NothingToClaim {},
+
+    #[error("Distribution has already been claimed")]
+    AlreadyClaimed {},
+
+    #[error("Claim deadline has passed")]
+    ClaimExpired {},
+
+    #[error("Claims are still open")]
+    ClaimPeriodOpen {},
+
+    #[error("Invalid milestones: {reason}")]
+    InvalidMilestones { reason: String },
+
+    #[error("Milestone {milestone:?} is not pending")]
+    MilestoneNotPending { milestone: u32 },
+
+    #[error("Distributions vest in this round, use WithdrawVested")]
+    VestingEnabled {},
+
+    #[error("Distributions do not vest in this round, use Claim")]
+    VestingDisabled {},
+
+    #[error("Nothing to distribute: the matching pool is empty and there are no votes")]
+    EmptyBudget {},
+
+    #[error("Matching in {denom} is too large to calculate")]
+    MatchingOverflow { denom: String },
+
+    #[error("Distributions in {denom} do not add up to the pool plus contributions")]
+    ConservationViolated { denom: String },
+
+    #[error("At least one contribution denom must be accepted")]
+    NoAcceptedDenoms {},
+
+    #[error("Denom {denom:?} is not accepted in this round")]
+    WrongDenom { denom: String },
+
+    #[error("Votes must be sent in a single denom")]
+    MultipleDenoms {},
+
+    #[error("No funds sent with vote")]
+    NoFunds {},
+
+    #[error("Vote is below the minimum contribution of {minimum}{denom}")]
+    BelowMinimum { minimum: Uint128, denom: String },
     // Add any other custom errors you like here.
     // Look at https://docs.rs/thiserror/1.0.21/thiserror/ for details.
 }
@@ -0,0 +1,241 @@
+use std::collections::BTreeMap;
+
+use cosmwasm_std::{Decimal, Uint128};
+
+use crate::error::ContractError;
+use crate::math::Fixed;
+use crate::state::{FundingFormula, Proposal, Vote};
+
+/// A matching formula. Votes passed in are normalized, so there is a single vote
+/// per voter and proposal, all in the same denom.
+pub trait Formula {
+    /// Matching each proposal would receive with an unlimited pool, in the same
+    /// order as `proposals`. None if it is too large to represent.
+    fn ideal_subsidies(&self, votes: &[Vote], proposals: &[Proposal]) -> Option<Vec<Fixed>>;
+
+    /// Matching each proposal receives from a pool of `budget`. The sum never
+    /// exceeds `budget`.
+    fn actual_subsidies(&self, ideal: &[Fixed], budget: u128) -> Option<Vec<u128>> {
+        scale_to_budget(ideal, budget)
+    }
+}
+
+/// Capital-constrained liberal radicalism: (sum of sqrt(contributions))^2.
+pub struct Quadratic;
+
+/// Matching proportional to the total contributed to each proposal.
+pub struct Linear;
+
+/// Quadratic funding where no proposal receives more than `cap` of the pool.
+pub struct CappedQuadratic {
+    pub cap: Fixed,
+}
+
+/// Pairwise-bounded quadratic funding (Buterin, Hitzig, Weyl). Quadratic matching
+/// for a proposal is the sum over each pair of its voters i, j of 2 * sqrt(c_i * c_j).
+/// Each pair's term is multiplied by M / (M + k_ij), where k_ij is the sum of
+/// sqrt(c_i * c_j) over every proposal the pair co-funds, so voters who
+/// repeatedly fund the same proposals together earn less matching. As M grows
+/// the result approaches plain quadratic funding.
+pub struct PairwiseBounded {
+    pub bound: Fixed,
+}
+
+impl FundingFormula {
+    pub fn validate(&self, accepted_denoms: &[String]) -> Result<(), ContractError> {
+        match self {
+            FundingFormula::CappedQuadratic { cap } => {
+                if cap.is_zero() || *cap > Decimal::one() {
+                    return Err(ContractError::InvalidFundingFormula {
+                        reason: "cap must be greater than 0 and at most 1".to_string(),
+                    });
+                }
+            }
+            FundingFormula::PairwiseBounded { bounds } => {
+                // M only means something relative to amounts in one denom.
+                let covered = accepted_denoms
+                    .iter()
+                    .all(|denom| bounds.iter().filter(|b| &b.denom == denom).count() == 1);
+                if !covered || bounds.len() != accepted_denoms.len() {
+                    return Err(ContractError::InvalidFundingFormula {
+                        reason: "there must be one bound for each accepted denom".to_string(),
+                    });
+                }
+                if bounds.iter().any(|b| b.amount.is_zero()) {
+                    return Err(ContractError::InvalidFundingFormula {
+                        reason: "bound must be greater than 0".to_string(),
+                    });
+                }
+            }
+            FundingFormula::Quadratic {} | FundingFormula::Linear {} => {}
+        }
+        Ok(())
+    }
+
+    // The formula for matching votes in `denom`.
+    pub fn formula(&self, denom: &str) -> Box<dyn Formula> {
+        match self {
+            FundingFormula::Quadratic {} => Box::new(Quadratic),
+            FundingFormula::Linear {} => Box::new(Linear),
+            FundingFormula::CappedQuadratic { cap } => Box::new(CappedQuadratic {
+                cap: decimal_to_fixed(*cap),
+            }),
+            FundingFormula::PairwiseBounded { bounds } => {
+                let bound = bounds
+                    .iter()
+                    .find(|b| b.denom == denom)
+                    .map(|b| b.amount.u128())
+                    .unwrap_or_default();
+                Box::new(PairwiseBounded {
+                    bound: Fixed::from_u128(bound),
+                })
+            }
+        }
+    }
+}
+
+// Decimal has the same 18 decimal places as Fixed, but its atomics are private.
+fn decimal_to_fixed(value: Decimal) -> Fixed {
+    let one = 1_000_000_000_000_000_000u128;
+    Fixed::from_ratio((Uint128(one) * value).u128(), one)
+}
+
+pub fn proposal_votes(votes: &[Vote], proposal_id: u32) -> Vec<u128> {
+    votes
+        .iter()
+        .filter(|v| v.proposal == proposal_id)
+        .map(|v| v.amount[0].amount.u128())
+        .collect()
+}
+
+// Splits `budget` between proposals in proportion to their ideal subsidy. Every
+// share is rounded down, so the sum never exceeds `budget` and the difference is
+// left as dust.
+pub fn scale_to_budget(ideal: &[Fixed], budget: u128) -> Option<Vec<u128>> {
+    let total: Fixed = ideal.iter().copied().sum();
+    if total.is_zero() {
+        return Some(vec![0; ideal.len()]);
+    }
+    ideal
+        .iter()
+        .map(|subsidy| subsidy.mul_div_floor(budget, total))
+        .collect()
+}
+
+impl Formula for Quadratic {
+    fn ideal_subsidies(&self, votes: &[Vote], proposals: &[Proposal]) -> Option<Vec<Fixed>> {
+        proposals
+            .iter()
+            .map(|p| {
+                let proposal_votes = proposal_votes(votes, p.id);
+                let sqrt_sum: Fixed = proposal_votes
+                    .iter()
+                    .map(|v| Fixed::from_u128(*v).sqrt())
+                    .sum();
+                let total_votes = Fixed::from_u128(proposal_votes.iter().sum());
+                Some(sqrt_sum.checked_mul(sqrt_sum)?.saturating_sub(total_votes))
+            })
+            .collect()
+    }
+}
+
+impl Formula for Linear {
+    fn ideal_subsidies(&self, votes: &[Vote], proposals: &[Proposal]) -> Option<Vec<Fixed>> {
+        Some(
+            proposals
+                .iter()
+                .map(|p| Fixed::from_u128(proposal_votes(votes, p.id).iter().sum()))
+                .collect(),
+        )
+    }
+}
+
+impl Formula for CappedQuadratic {
+    fn ideal_subsidies(&self, votes: &[Vote], proposals: &[Proposal]) -> Option<Vec<Fixed>> {
+        Quadratic.ideal_subsidies(votes, proposals)
+    }
+
+    // Proposals over the cap are fixed at the cap and the excess is shared between
+    // the remaining proposals, until no proposal is over the cap.
+    fn actual_subsidies(&self, ideal: &[Fixed], budget: u128) -> Option<Vec<u128>> {
+        let cap = self.cap.mul_div_floor(budget, Fixed::from_u128(1))?;
+        let mut actual = vec![0u128; ideal.len()];
+        let mut capped = vec![false; ideal.len()];
+        let mut remaining = budget;
+        loop {
+            let open: Vec<usize> = (0..ideal.len()).filter(|i| !capped[*i]).collect();
+            let open_ideal: Vec<Fixed> = open.iter().map(|i| ideal[*i]).collect();
+            if open_ideal.iter().all(Fixed::is_zero) || remaining == 0 {
+                break;
+            }
+            let scaled = scale_to_budget(&open_ideal, remaining)?;
+            let over: Vec<usize> = open
+                .iter()
+                .zip(scaled.iter())
+                .filter(|(_, s)| **s > cap)
+                .map(|(i, _)| *i)
+                .collect();
+            if over.is_empty() {
+                for (i, s) in open.iter().zip(scaled) {
+                    actual[*i] = s;
+                }
+                break;
+            }
+            for i in over {
+                actual[i] = cap;
+                capped[i] = true;
+                remaining -= cap;
+            }
+        }
+        Some(actual)
+    }
+}
+
+impl Formula for PairwiseBounded {
+    fn ideal_subsidies(&self, votes: &[Vote], proposals: &[Proposal]) -> Option<Vec<Fixed>> {
+        // How much each pair of voters co-funds across all proposals.
+        let mut pair_totals: BTreeMap<(Vec<u8>, Vec<u8>), Fixed> = BTreeMap::new();
+        for p in proposals {
+            let contributors: Vec<&Vote> = votes.iter().filter(|v| v.proposal == p.id).collect();
+            for (i, a) in contributors.iter().enumerate() {
+                for b in contributors.iter().skip(i + 1) {
+                    *pair_totals.entry(pair_key(a, b)).or_default() += pair_sqrt(a, b);
+                }
+            }
+        }
+
+        proposals
+            .iter()
+            .map(|p| {
+                let contributors: Vec<&Vote> =
+                    votes.iter().filter(|v| v.proposal == p.id).collect();
+                let mut subsidy = Fixed::zero();
+                for (i, a) in contributors.iter().enumerate() {
+                    for b in contributors.iter().skip(i + 1) {
+                        let co_funded = pair_totals[&pair_key(a, b)];
+                        let damping = self.bound.checked_div(self.bound + co_funded)?;
+                        subsidy += Fixed::from_u128(2)
+                            .checked_mul(pair_sqrt(a, b))?
+                            .checked_mul(damping)?;
+                    }
+                }
+                Some(subsidy)
+            })
+            .collect()
+    }
+}
+
+fn pair_key(a: &Vote, b: &Vote) -> (Vec<u8>, Vec<u8>) {
+    let a = a.voter.as_slice().to_vec();
+    let b = b.voter.as_slice().to_vec();
+    if a < b {
+        (a, b)
+    } else {
+        (b, a)
+    }
+}
+
+fn pair_sqrt(a: &Vote, b: &Vote) -> Fixed {
+    Fixed::from_u128(a.amount[0].amount.u128())
+        .sqrt_product(Fixed::from_u128(b.amount[0].amount.u128()))
+}
@@ -0,0 +1,43 @@
+use cosmwasm_std::{HumanAddr, Querier, StdResult};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+// An external cw4 group whose members can propose and vote, as well as those in
+// the whitelists. Members need at least `min_weight`, if set.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct Group {
+    pub addr: HumanAddr,
+    pub min_weight: Option<u64>,
+}
+
+// The subset of the cw4 query interface used here.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum Cw4QueryMsg {
+    Member {
+        addr: HumanAddr,
+        at_height: Option<u64>,
+    },
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct MemberResponse {
+    // None if the address isn't a member.
+    pub weight: Option<u64>,
+}
+
+impl Group {
+    pub fn is_member<Q: Querier>(&self, querier: &Q, addr: &HumanAddr) -> StdResult<bool> {
+        let res: MemberResponse = querier.query_wasm_smart(
+            self.addr.clone(),
+            &Cw4QueryMsg::Member {
+                addr: addr.clone(),
+                at_height: None,
+            },
+        )?;
+        Ok(match res.weight {
+            Some(weight) => weight >= self.min_weight.unwrap_or(0),
+            None => false,
+        })
+    }
+}
@@ -1,7 +1,13 @@
 pub mod contract;
 pub mod error;
+pub mod formula;
+pub mod group;
+pub mod math;
+pub mod merkle;
 pub mod msg;
+pub mod phase;
 pub mod state;
+pub mod vesting;
 
 #[cfg(test)]
 mod tests;
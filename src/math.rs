@@ -0,0 +1,145 @@
+use std::convert::TryFrom;
+use std::iter::Sum;
+use std::ops;
+
+// The generated code trips lints we have no control over.
+#[allow(clippy::all)]
+mod uint256 {
+    uint::construct_uint! {
+        pub struct U256(4);
+    }
+    uint::construct_uint! {
+        pub struct U512(8);
+    }
+}
+
+pub use uint256::{U256, U512};
+
+// 10^18, the scale of a Fixed value.
+const FRACTIONAL: u128 = 1_000_000_000_000_000_000u128;
+
+/// Unsigned fixed-point number with 18 decimal places, backed by a 256 bit
+/// integer. Products and quotients are worked out in 512 bits, so they only fail
+/// when the result itself doesn't fit. All operations round down.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Fixed(U256);
+
+impl Fixed {
+    pub fn zero() -> Self {
+        Fixed(U256::zero())
+    }
+
+    pub fn from_u128(value: u128) -> Self {
+        Fixed(U256::from(value) * U256::from(FRACTIONAL))
+    }
+
+    pub fn from_ratio(numerator: u128, denominator: u128) -> Self {
+        Fixed(U256::from(numerator) * U256::from(FRACTIONAL) / U256::from(denominator))
+    }
+
+    pub fn is_zero(&self) -> bool {
+        self.0.is_zero()
+    }
+
+    /// The integer part of the value.
+    pub fn floor(&self) -> u128 {
+        (self.0 / U256::from(FRACTIONAL)).as_u128()
+    }
+
+    /// The integer part of the value, or None if it doesn't fit in a u128.
+    pub fn checked_floor(&self) -> Option<u128> {
+        u128::try_from(self.0 / U256::from(FRACTIONAL)).ok()
+    }
+
+    pub fn sqrt(&self) -> Self {
+        // sqrt(x * 10^18) * 10^9 loses precision, so scale before taking the root.
+        Fixed::root(widen(self.0) * U512::from(FRACTIONAL))
+    }
+
+    /// sqrt(self * other), which never overflows even when the product would.
+    pub fn sqrt_product(self, other: Self) -> Self {
+        Fixed::root(widen(self.0) * widen(other.0))
+    }
+
+    // The root of a 512 bit number always fits in 256 bits.
+    fn root(value: U512) -> Self {
+        Fixed(narrow(value.integer_sqrt()).expect("square root fits in 256 bits"))
+    }
+
+    pub fn checked_mul(self, other: Self) -> Option<Self> {
+        narrow(widen(self.0) * widen(other.0) / U512::from(FRACTIONAL)).map(Fixed)
+    }
+
+    pub fn checked_div(self, other: Self) -> Option<Self> {
+        if other.is_zero() {
+            return None;
+        }
+        narrow(widen(self.0) * U512::from(FRACTIONAL) / widen(other.0)).map(Fixed)
+    }
+
+    /// floor(self * numerator / denominator) as an integer, without rounding the
+    /// intermediate ratio.
+    pub fn mul_div_floor(self, numerator: u128, denominator: Self) -> Option<u128> {
+        if denominator.is_zero() {
+            return None;
+        }
+        let result = widen(self.0) * U512::from(numerator) / widen(denominator.0);
+        narrow(result).and_then(|result| u128::try_from(result).ok())
+    }
+
+    pub fn saturating_sub(self, other: Self) -> Self {
+        match self > other {
+            true => self - other,
+            false => Fixed::zero(),
+        }
+    }
+}
+
+impl From<u128> for Fixed {
+    fn from(value: u128) -> Self {
+        Fixed::from_u128(value)
+    }
+}
+
+impl ops::Add for Fixed {
+    type Output = Self;
+
+    fn add(self, other: Self) -> Self {
+        Fixed(self.0 + other.0)
+    }
+}
+
+impl ops::AddAssign for Fixed {
+    fn add_assign(&mut self, other: Self) {
+        self.0 = self.0 + other.0;
+    }
+}
+
+impl ops::Sub for Fixed {
+    type Output = Self;
+
+    fn sub(self, other: Self) -> Self {
+        Fixed(self.0 - other.0)
+    }
+}
+
+impl Sum for Fixed {
+    fn sum<I: Iterator<Item = Self>>(iter: I) -> Self {
+        iter.fold(Fixed::zero(), |total, x| total + x)
+    }
+}
+
+fn widen(value: U256) -> U512 {
+    let mut bytes = [0u8; 32];
+    value.to_little_endian(&mut bytes);
+    U512::from_little_endian(&bytes)
+}
+
+fn narrow(value: U512) -> Option<U256> {
+    if value.bits() > 256 {
+        return None;
+    }
+    let mut bytes = [0u8; 64];
+    value.to_little_endian(&mut bytes);
+    Some(U256::from_little_endian(&bytes[..32]))
+}
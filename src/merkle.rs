@@ -0,0 +1,69 @@
+use cosmwasm_std::{Coin, HumanAddr};
+use sha2::{Digest, Sha256};
+
+pub type Hash = [u8; 32];
+
+// Leaf for an eligible voter, committing to their contribution cap if they have one.
+pub fn leaf(voter: &HumanAddr, cap: Option<&Coin>) -> Hash {
+    let data = match cap {
+        Some(cap) => format!("{}:{}{}", voter, cap.amount, cap.denom),
+        None => voter.to_string(),
+    };
+    Sha256::digest(data.as_bytes()).into()
+}
+
+// Pairs are hashed in sorted order, so a proof doesn't need to say which side
+// each sibling is on.
+fn hash_pair(a: &Hash, b: &Hash) -> Hash {
+    let (first, second) = if a <= b { (a, b) } else { (b, a) };
+    let mut hasher = Sha256::new();
+    hasher.update(first);
+    hasher.update(second);
+    hasher.finalize().into()
+}
+
+pub fn verify(root: &Hash, leaf: Hash, proof: &[Hash]) -> bool {
+    proof
+        .iter()
+        .fold(leaf, |node, sibling| hash_pair(&node, sibling))
+        == *root
+}
+
+// Hashes are passed around hex encoded.
+pub fn parse_hash(hash: &str) -> Option<Hash> {
+    let bytes = hex::decode(hash).ok()?;
+    if bytes.len() != 32 {
+        return None;
+    }
+    let mut parsed = [0u8; 32];
+    parsed.copy_from_slice(&bytes);
+    Some(parsed)
+}
+
+// Builds the root of a tree over `leaves` and the proof for each leaf, in order.
+// A node without a sibling is carried up a level unchanged. Only used off chain,
+// e.g. to set up a round in tests.
+#[cfg(not(target_arch = "wasm32"))]
+pub fn build(leaves: &[Hash]) -> (Hash, Vec<Vec<Hash>>) {
+    assert!(!leaves.is_empty(), "a tree needs at least one leaf");
+    let mut proofs = vec![vec![]; leaves.len()];
+    // Where each leaf's ancestor is on the current level.
+    let mut positions: Vec<usize> = (0..leaves.len()).collect();
+    let mut level = leaves.to_vec();
+    while level.len() > 1 {
+        for (proof, position) in proofs.iter_mut().zip(positions.iter_mut()) {
+            if let Some(sibling) = level.get(*position ^ 1) {
+                proof.push(*sibling);
+            }
+            *position /= 2;
+        }
+        level = level
+            .chunks(2)
+            .map(|pair| match pair {
+                [a, b] => hash_pair(a, b),
+                _ => pair[0],
+            })
+            .collect();
+    }
+    (level[0], proofs)
+}
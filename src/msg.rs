@@ -1,5 +1,11 @@
-use crate::state::{Distribution, Proposal, Vote};
-use cosmwasm_std::HumanAddr;
+use crate::group::Group;
+use crate::phase::{Expiration, PhaseDeadline, RoundPhase};
+use crate::state::{
+    Claim, Distribution, DistributionSnapshot, FundingFormula, LeftoverPolicy, Milestone, Proposal,
+    Role, Vote,
+};
+use crate::vesting::VestingSchedule;
+use cosmwasm_std::{Coin, HumanAddr};
 use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 
@@ -10,41 +16,130 @@ pub struct InitMsg {
     pub name: String,
     pub proposer_whitelist: Vec<HumanAddr>,
     pub voter_whitelist: Vec<HumanAddr>,
+    // Ignore the whitelist and let anyone create proposals or vote.
+    pub open_proposals: bool,
+    pub open_voting: bool,
+    // Voters in this tree can vote as well as those in the whitelist. See merkle.rs
+    // for how leaves are built. Can't be combined with open_voting. Caps in the
+    // tree only bind voters who vote with a proof, so whitelist and group members
+    // are uncapped.
+    pub voter_merkle_root: Option<String>,
+    // Members of a cw4 group can propose and vote, instead of copying them into
+    // the whitelists.
+    pub group: Option<Group>,
     // pub proposal_min_period: Option<u32>,
     // pub voting_min_period: Option<u32>,
-    pub proposal_period_start: Option<u64>,
-    pub proposal_period_end: Option<u64>,
-    pub voting_period_start: Option<u64>,
-    pub voting_period_end: Option<u64>,
-    // pub funding_formula: Option<String>,
+    pub proposal_period_start: Option<Expiration>,
+    pub proposal_period_end: Option<Expiration>,
+    pub voting_period_start: Option<Expiration>,
+    pub voting_period_end: Option<Expiration>,
+    // Defaults to quadratic funding.
+    pub funding_formula: Option<FundingFormula>,
+    // Denoms votes may be sent in. The balance in each is matched separately.
+    pub accepted_denoms: Vec<String>,
+    // Smallest vote allowed in each denom. Denoms not listed have no minimum.
+    pub min_contribution: Vec<Coin>,
+    // Unclaimed distributions can be returned to the owner after this bound.
+    pub claim_deadline: Option<Expiration>,
+    // Vest distributions over this schedule instead of paying them out at once.
+    pub vesting: Option<VestingSchedule>,
+    // Addresses besides the owner that can approve proposals and release milestones.
+    pub reviewers: Vec<HumanAddr>,
+    // Proposals can only be voted on once a reviewer approves them.
+    pub require_approval: bool,
+    // Defaults to returning leftovers to sponsors.
+    pub leftover_policy: Option<LeftoverPolicy>,
+    // Addresses besides the owner that can change periods.
+    pub operators: Vec<HumanAddr>,
+    // Addresses besides the owner that can distribute funds.
+    pub treasurers: Vec<HumanAddr>,
 }
 
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
 #[serde(rename_all = "snake_case")]
 pub enum HandleMsg {
     StartProposalPeriod {
-        time: Option<u64>,
+        at: Option<Expiration>,
     },
     EndProposalPeriod {
-        time: Option<u64>,
+        at: Option<Expiration>,
     },
     StartVotingPeriod {
-        time: Option<u64>,
+        at: Option<Expiration>,
     },
     EndVotingPeriod {
-        time: Option<u64>,
+        at: Option<Expiration>,
     },
     CreateProposal {
         name: String,
         recipient: HumanAddr,
         description: String,
         tags: String,
+        // Shares must add up to 1.
+        milestones: Option<Vec<Milestone>>,
     },
     CreateVote {
         proposal_id: u32,
+        // Only needed when voting as part of the voter merkle tree.
+        proof: Option<VoterProof>,
     },
+    // Adds the sent funds to the matching pool, recorded under the sender.
+    FundPool {},
     CheckDistributions {},
     DistributeFunds {},
+    // Proposers can only change until the proposal period ends.
+    UpdateProposerWhitelist {
+        add: Vec<HumanAddr>,
+        remove: Vec<HumanAddr>,
+    },
+    // Voters can only change until voting starts.
+    UpdateVoterWhitelist {
+        add: Vec<HumanAddr>,
+        remove: Vec<HumanAddr>,
+    },
+    // Offers ownership to `owner`, who becomes owner once they accept.
+    ProposeOwner {
+        owner: HumanAddr,
+    },
+    AcceptOwnership {},
+    UpdateRole {
+        role: Role,
+        add: Vec<HumanAddr>,
+        remove: Vec<HumanAddr>,
+    },
+    // Ends the round without distributing, so contributions can be refunded.
+    CancelRound {},
+    // Returns the sender's votes and sponsor deposits after the round is cancelled.
+    ClaimRefund {},
+    // Pays out a proposal's distribution to its recipient.
+    Claim {
+        proposal_id: u32,
+    },
+    // Pays out the vested part of a proposal's distribution to its recipient.
+    WithdrawVested {
+        proposal_id: u32,
+    },
+    // Lets a proposal be voted on and matched, in rounds that require approval.
+    ApproveProposal {
+        proposal_id: u32,
+    },
+    // Makes a milestone's share of a proposal's distribution claimable.
+    ReleaseMilestone {
+        proposal_id: u32,
+        milestone: u32,
+    },
+    // Takes back the unreleased milestones of an abandoned proposal and pays them out
+    // under the leftover policy.
+    ClawBack {
+        proposal_id: u32,
+    },
+    // Sends distributions nobody claimed before the deadline back to the owner.
+    ReturnUnclaimed {},
+    // Moves pending leftovers into the successor round's pool. If the successor
+    // won't accept them, `to_sponsors` splits them between sponsors instead.
+    SendRollover {
+        to_sponsors: bool,
+    },
 }
 
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
@@ -53,6 +148,7 @@ pub struct CreateProposal {
     pub recipient: HumanAddr,
     pub description: String,
     pub tags: String,
+    pub milestones: Option<Vec<Milestone>>,
 }
 
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
@@ -63,14 +159,66 @@ pub struct CreateProposalResponse {
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
 pub struct CheckDistributionsResponse {
     pub distributions: Vec<Distribution>,
+    // Matching that is not distributed because every subsidy is rounded down.
+    pub dust: Vec<Coin>,
 }
 
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
 #[serde(rename_all = "snake_case")]
 pub enum QueryMsg {
     GetState {},
-    ProposalList {},
-    ProposalState { proposal_id: u32 },
+    ProposalList {
+        start_after: Option<u32>,
+        limit: Option<u32>,
+    },
+    ProposalState {
+        proposal_id: u32,
+    },
+    VotesByProposal {
+        proposal_id: u32,
+        start_after: Option<HumanAddr>,
+        limit: Option<u32>,
+    },
+    VotesByVoter {
+        voter: HumanAddr,
+        start_after: Option<u32>,
+        limit: Option<u32>,
+    },
+    FinalDistributions {},
+    // Preview distributions against the current balance, optionally including a vote
+    // that has not been cast yet.
+    Distributions {
+        hypothetical_vote: Option<HypotheticalVote>,
+    },
+    Phase {},
+    Claims {
+        start_after: Option<u32>,
+        limit: Option<u32>,
+    },
+    Vesting {
+        proposal_id: u32,
+    },
+    Sponsors {
+        start_after: Option<HumanAddr>,
+        limit: Option<u32>,
+    },
+    Roles {},
+}
+
+// Proof that the sender is a leaf of the voter merkle tree.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct VoterProof {
+    // Hex encoded sibling hashes, from the leaf up.
+    pub proof: Vec<String>,
+    // The voter's contribution cap, if their leaf has one.
+    pub cap: Option<Coin>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct HypotheticalVote {
+    pub voter: HumanAddr,
+    pub proposal_id: u32,
+    pub amount: Coin,
 }
 
 // // We define a custom struct for each query response
@@ -85,19 +233,100 @@ pub struct StateResponse {
     pub name: String,
     pub proposer_whitelist: Vec<HumanAddr>,
     pub voter_whitelist: Vec<HumanAddr>,
-    pub proposal_period_start: Option<u64>,
-    pub proposal_period_end: Option<u64>,
-    pub voting_period_start: Option<u64>,
-    pub voting_period_end: Option<u64>,
+    pub open_proposals: bool,
+    pub open_voting: bool,
+    pub voter_merkle_root: Option<String>,
+    pub group: Option<Group>,
+    pub proposal_period_start: Option<Expiration>,
+    pub proposal_period_end: Option<Expiration>,
+    pub voting_period_start: Option<Expiration>,
+    pub voting_period_end: Option<Expiration>,
+    pub funding_formula: FundingFormula,
+    pub accepted_denoms: Vec<String>,
+    pub min_contribution: Vec<Coin>,
+    pub claim_deadline: Option<Expiration>,
+    pub vesting: Option<VestingSchedule>,
+    pub reviewers: Vec<HumanAddr>,
+    pub require_approval: bool,
+    pub leftover_policy: LeftoverPolicy,
 }
 
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
 pub struct ProposalListResponse {
     pub proposals: Vec<Proposal>,
+    // Pass as `start_after` to fetch the next page, None on the last page.
+    pub next_start_after: Option<u32>,
 }
 
+// Votes aren't included, as there is no bound on how many a proposal has. Page
+// through them with VotesByProposal.
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
 pub struct ProposalStateResponse {
     pub proposal: Proposal,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct VotesByProposalResponse {
+    pub votes: Vec<Vote>,
+    pub next_start_after: Option<HumanAddr>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct VotesByVoterResponse {
     pub votes: Vec<Vote>,
+    pub next_start_after: Option<u32>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct FinalDistributionsResponse {
+    pub snapshot: DistributionSnapshot,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct ClaimsResponse {
+    pub claims: Vec<Claim>,
+    pub deadline: Option<Expiration>,
+    pub next_start_after: Option<u32>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct RolesResponse {
+    pub owner: HumanAddr,
+    pub pending_owner: Option<HumanAddr>,
+    pub operators: Vec<HumanAddr>,
+    pub treasurers: Vec<HumanAddr>,
+    pub reviewers: Vec<HumanAddr>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct SponsorDeposit {
+    pub sponsor: HumanAddr,
+    pub amount: Vec<Coin>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct SponsorsResponse {
+    pub sponsors: Vec<SponsorDeposit>,
+    // Size of the whole matching pool, across every sponsor.
+    pub total: Vec<Coin>,
+    pub next_start_after: Option<HumanAddr>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct VestingResponse {
+    pub proposal_id: u32,
+    pub schedule: VestingSchedule,
+    pub total: Vec<Coin>,
+    pub vested: Vec<Coin>,
+    pub withdrawn: Vec<Coin>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct PhaseResponse {
+    pub phase: RoundPhase,
+    // The block the phase was computed at, so deadlines in either unit can be compared.
+    pub height: u64,
+    pub time: u64,
+    // Scheduled phase changes that have not happened yet, in order.
+    pub deadlines: Vec<PhaseDeadline>,
 }
@@ -0,0 +1,263 @@
+use cosmwasm_std::{BlockInfo, ReadonlyStorage, StdResult};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+use crate::error::ContractError;
+use crate::state::{distribution_snapshot_read, State};
+
+// A round moves through these phases in order. Every handler checks the current
+// phase here rather than comparing period times itself.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum RoundPhase {
+    // Before the proposal period starts.
+    Setup,
+    Proposals,
+    // Between the proposal and voting periods.
+    Review,
+    Voting,
+    // After voting ends, until funds are distributed.
+    Tally,
+    Distributed,
+    Cancelled,
+}
+
+impl RoundPhase {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            RoundPhase::Setup => "setup",
+            RoundPhase::Proposals => "proposals",
+            RoundPhase::Review => "review",
+            RoundPhase::Voting => "voting",
+            RoundPhase::Tally => "tally",
+            RoundPhase::Distributed => "distributed",
+            RoundPhase::Cancelled => "cancelled",
+        }
+    }
+}
+
+// A period bound, given either as a block height or a block time in seconds.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum Expiration {
+    AtHeight(u64),
+    AtTime(u64),
+}
+
+impl Expiration {
+    // The same kind of bound as `self`, at the current block.
+    pub fn now(&self, block: &BlockInfo) -> Expiration {
+        match self {
+            Expiration::AtHeight(_) => Expiration::AtHeight(block.height),
+            Expiration::AtTime(_) => Expiration::AtTime(block.time),
+        }
+    }
+
+    // Whether the block is at or past the bound.
+    pub fn is_reached(&self, block: &BlockInfo) -> bool {
+        match self {
+            Expiration::AtHeight(height) => block.height >= *height,
+            Expiration::AtTime(time) => block.time >= *time,
+        }
+    }
+
+    // Whether the block is strictly past the bound. Period ends are inclusive.
+    pub fn is_expired(&self, block: &BlockInfo) -> bool {
+        match self {
+            Expiration::AtHeight(height) => block.height > *height,
+            Expiration::AtTime(time) => block.time > *time,
+        }
+    }
+
+    // The first block height or time after the bound. A bound of u64::MAX never
+    // passes, so it is its own successor.
+    pub fn next(&self) -> Expiration {
+        match self {
+            Expiration::AtHeight(height) => Expiration::AtHeight(height.saturating_add(1)),
+            Expiration::AtTime(time) => Expiration::AtTime(time.saturating_add(1)),
+        }
+    }
+
+    pub(crate) fn value(&self) -> u64 {
+        match self {
+            Expiration::AtHeight(height) => *height,
+            Expiration::AtTime(time) => *time,
+        }
+    }
+
+    pub(crate) fn same_kind(&self, other: &Expiration) -> bool {
+        matches!(
+            (self, other),
+            (Expiration::AtHeight(_), Expiration::AtHeight(_))
+                | (Expiration::AtTime(_), Expiration::AtTime(_))
+        )
+    }
+}
+
+// A scheduled move into `phase`.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct PhaseDeadline {
+    pub phase: RoundPhase,
+    pub at: Expiration,
+}
+
+fn period_started(block: &BlockInfo, period_start: Option<Expiration>) -> bool {
+    match period_start {
+        Some(start) => start.is_reached(block),
+        None => false,
+    }
+}
+
+fn period_ended(block: &BlockInfo, period_end: Option<Expiration>) -> bool {
+    match period_end {
+        Some(end) => end.is_expired(block),
+        None => false,
+    }
+}
+
+// The phase of the round at `block`. Periods are only considered in order, so a
+// later period never applies before the earlier ones are over.
+pub fn current_phase<S: ReadonlyStorage>(
+    storage: &S,
+    state: &State,
+    block: &BlockInfo,
+) -> StdResult<RoundPhase> {
+    if distribution_snapshot_read(storage).may_load()?.is_some() {
+        return Ok(RoundPhase::Distributed);
+    }
+    if state.cancelled {
+        return Ok(RoundPhase::Cancelled);
+    }
+    let phase = if !period_started(block, state.proposal_period_start) {
+        RoundPhase::Setup
+    } else if !period_ended(block, state.proposal_period_end) {
+        RoundPhase::Proposals
+    } else if !period_started(block, state.voting_period_start) {
+        RoundPhase::Review
+    } else if !period_ended(block, state.voting_period_end) {
+        RoundPhase::Voting
+    } else {
+        RoundPhase::Tally
+    };
+    Ok(phase)
+}
+
+// Scheduled phase changes that have not happened yet, in order.
+pub fn upcoming_deadlines(state: &State, block: &BlockInfo) -> Vec<PhaseDeadline> {
+    let schedule = [
+        (RoundPhase::Proposals, state.proposal_period_start),
+        (
+            RoundPhase::Review,
+            state.proposal_period_end.map(|e| e.next()),
+        ),
+        (RoundPhase::Voting, state.voting_period_start),
+        (RoundPhase::Tally, state.voting_period_end.map(|e| e.next())),
+    ];
+    schedule
+        .iter()
+        .filter_map(|(phase, at)| at.map(|at| PhaseDeadline { phase: *phase, at }))
+        .filter(|deadline| !deadline.at.is_reached(block))
+        .collect()
+}
+
+// Bound for a period handler: `at` if given, otherwise the current block, in the
+// same units as the rest of the schedule.
+pub fn period_bound(state: &State, at: Option<Expiration>, block: &BlockInfo) -> Expiration {
+    match at {
+        Some(at) => at,
+        None => schedule(state)
+            .first()
+            .map(|bound| bound.now(block))
+            .unwrap_or(Expiration::AtTime(block.time)),
+    }
+}
+
+fn schedule(state: &State) -> Vec<Expiration> {
+    [
+        state.proposal_period_start,
+        state.proposal_period_end,
+        state.voting_period_start,
+        state.voting_period_end,
+        state.claim_deadline,
+    ]
+    .iter()
+    .filter_map(|bound| *bound)
+    .collect()
+}
+
+// Fails unless the round is in one of the `allowed` phases.
+pub fn require_phase(phase: RoundPhase, allowed: &[RoundPhase]) -> Result<(), ContractError> {
+    if phase == RoundPhase::Distributed && !allowed.contains(&phase) {
+        return Err(ContractError::AlreadyDistributed {});
+    }
+    if phase == RoundPhase::Cancelled && !allowed.contains(&phase) {
+        return Err(ContractError::RoundCancelled {});
+    }
+    if !allowed.contains(&phase) {
+        return Err(ContractError::InvalidPeriod {
+            period_type: allowed
+                .iter()
+                .map(RoundPhase::as_str)
+                .collect::<Vec<_>>()
+                .join(" or "),
+        });
+    }
+    Ok(())
+}
+
+// Checks that the configured periods are each well formed and that voting only
+// starts once the proposal period is over.
+pub fn validate_schedule(state: &State) -> Result<(), ContractError> {
+    // Heights and times can't be compared, so a round uses one or the other.
+    let bounds = schedule(state);
+    if bounds.iter().any(|bound| !bound.same_kind(&bounds[0])) {
+        return Err(invalid_schedule(
+            "periods must all be set by height or all by time",
+        ));
+    }
+    // Every set bound has to come after each earlier one in schedule order, even
+    // when the bounds between them are unset. Period ends are inclusive, so
+    // only a period's own end, and voting against the proposal start, may
+    // share a bound with an earlier one.
+    let named = [
+        (state.proposal_period_start, "proposal period start"),
+        (state.proposal_period_end, "proposal period end"),
+        (state.voting_period_start, "voting period start"),
+        (state.voting_period_end, "voting period end"),
+        (state.claim_deadline, "claim deadline"),
+    ];
+    for (i, (earlier, earlier_name)) in named.iter().enumerate() {
+        for (j, (later, later_name)) in named.iter().enumerate().skip(i + 1) {
+            if let (Some(earlier), Some(later)) = (earlier, later) {
+                let inclusive = (i == 0 && j != 4) || (i, j) == (2, 3);
+                if later.value() < earlier.value() || !inclusive && later.value() == earlier.value()
+                {
+                    return Err(ContractError::InvalidSchedule {
+                        reason: format!(
+                            "{} must be {} the {}",
+                            later_name,
+                            if inclusive { "at or after" } else { "after" },
+                            earlier_name
+                        ),
+                    });
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+// A bound set by a period handler can't be in the past, so a period that
+// already happened can't be rewritten.
+pub fn validate_new_bound(bound: &Expiration, block: &BlockInfo) -> Result<(), ContractError> {
+    if bound.value() < bound.now(block).value() {
+        return Err(invalid_schedule("period bounds cannot be in the past"));
+    }
+    Ok(())
+}
+
+fn invalid_schedule(reason: &str) -> ContractError {
+    ContractError::InvalidSchedule {
+        reason: reason.to_string(),
+    }
+}
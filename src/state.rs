@@ -1,10 +1,28 @@
 use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 
-use cosmwasm_std::{CanonicalAddr, Coin, Storage};
-use cosmwasm_storage::{singleton, singleton_read, ReadonlySingleton, Singleton};
+use cosmwasm_std::{
+    CanonicalAddr, Coin, Decimal, HumanAddr, Order, ReadonlyStorage, StdResult, Storage,
+};
+use cosmwasm_storage::{
+    bucket, bucket_read, sequence, singleton, singleton_read, Bucket, ReadonlyBucket,
+    ReadonlySingleton, Singleton,
+};
+
+use crate::group::Group;
+use crate::phase::Expiration;
+use crate::vesting::VestingSchedule;
 
 pub static CONFIG_KEY: &[u8] = b"config";
+pub static PROPOSAL_KEY: &[u8] = b"proposal";
+pub static PROPOSAL_SEQ_KEY: &[u8] = b"proposal_seq";
+pub static VOTE_KEY: &[u8] = b"vote";
+pub static VOTER_INDEX_KEY: &[u8] = b"voter_index";
+pub static DISTRIBUTION_KEY: &[u8] = b"distribution";
+pub static SPONSOR_KEY: &[u8] = b"sponsor";
+pub static REFUND_KEY: &[u8] = b"refund";
+pub static CLAIM_KEY: &[u8] = b"claim";
+pub static POOL_KEY: &[u8] = b"pool";
 
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
 pub struct State {
@@ -15,17 +33,98 @@ pub struct State {
     pub name: String,
     pub proposer_whitelist: Vec<CanonicalAddr>,
     pub voter_whitelist: Vec<CanonicalAddr>,
+    // Anyone can propose or vote when set. An empty whitelist otherwise allows nobody.
+    pub open_proposals: bool,
+    pub open_voting: bool,
+    // Hex encoded root of a merkle tree of eligible voters, for lists too large
+    // to store. Voters prove they are in it when voting.
+    pub voter_merkle_root: Option<String>,
+    // Members of this group can propose and vote as well.
+    pub group: Option<Group>,
     // // pub voting_min_period: u32, // in seconds
     // // pub proposal_min_period: u32,
     // // pub min_voting_period: u32,
     // // pub min_proposal_period: u32,
-    pub proposal_period_start: Option<u64>, // Option values are optional
-    pub proposal_period_end: Option<u64>,
-    pub voting_period_start: Option<u64>,
-    pub voting_period_end: Option<u64>,
-    // pub funding_formula: Option<String>,
-    pub proposals: Vec<Proposal>,
-    pub votes: Vec<Vote>,
+    pub proposal_period_start: Option<Expiration>, // Option values are optional
+    pub proposal_period_end: Option<Expiration>,
+    pub voting_period_start: Option<Expiration>,
+    pub voting_period_end: Option<Expiration>,
+    pub funding_formula: FundingFormula,
+    pub accepted_denoms: Vec<String>,
+    pub min_contribution: Vec<Coin>,
+    // Set by CancelRound. A cancelled round only pays out refunds.
+    pub cancelled: bool,
+    // Recipients must claim their distribution by this bound, if set.
+    pub claim_deadline: Option<Expiration>,
+    // If set, distributions vest over time instead of being claimed at once.
+    pub vesting: Option<VestingSchedule>,
+    // Can approve proposals and release milestones, along with the owner.
+    pub reviewers: Vec<CanonicalAddr>,
+    // New proposals wait for a reviewer's approval when set.
+    pub require_approval: bool,
+    pub leftover_policy: LeftoverPolicy,
+    // Set by ProposeOwner until the new owner accepts.
+    pub pending_owner: Option<CanonicalAddr>,
+    // Can change periods, along with the owner.
+    pub operators: Vec<CanonicalAddr>,
+    // Can distribute funds, along with the owner.
+    pub treasurers: Vec<CanonicalAddr>,
+}
+
+// Admin rights the owner can hand out, so the round isn't run from one key.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum Role {
+    Operator,
+    Treasurer,
+    Reviewer,
+}
+
+impl Role {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Role::Operator => "operator",
+            Role::Treasurer => "treasurer",
+            Role::Reviewer => "reviewer",
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum FundingFormula {
+    Quadratic {},
+    Linear {},
+    // cap is the largest share of the pool a single proposal can receive.
+    CappedQuadratic { cap: Decimal },
+    // bounds holds the pairwise coordination bound M for each accepted denom, as
+    // an amount of that denom.
+    PairwiseBounded { bounds: Vec<Coin> },
+}
+
+impl Default for FundingFormula {
+    fn default() -> Self {
+        FundingFormula::Quadratic {}
+    }
+}
+
+// Where the pool's unmatched remainder and rounding dust go when funds are distributed.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum LeftoverPolicy {
+    // Split between sponsors in proportion to their deposits. A denom no sponsor
+    // deposited goes to the owner.
+    Sponsors {},
+    Treasury { address: HumanAddr },
+    // Added to a successor round's matching pool with FundPool, once SendRollover
+    // is called.
+    Rollover { round: HumanAddr },
+}
+
+impl Default for LeftoverPolicy {
+    fn default() -> Self {
+        LeftoverPolicy::Sponsors {}
+    }
 }
 
 #[derive(Serialize, Deserialize, Clone, PartialEq, JsonSchema, Debug)]
@@ -35,6 +134,17 @@ pub struct Proposal {
     pub recipient: CanonicalAddr,
     pub description: String,
     pub tags: String,
+    // Empty unless the proposal's distribution is released in stages.
+    pub milestones: Vec<Milestone>,
+    // Only approved proposals accept votes and are matched.
+    pub approved: bool,
+}
+
+// A deliverable unlocking `share` of a proposal's distribution.
+#[derive(Serialize, Deserialize, Clone, PartialEq, JsonSchema, Debug)]
+pub struct Milestone {
+    pub description: String,
+    pub share: Decimal,
 }
 
 #[derive(Serialize, Deserialize, Clone, PartialEq, JsonSchema, Debug)]
@@ -57,10 +167,194 @@ pub struct Distribution {
     pub subsidy_actual: Coin,
 }
 
-pub fn config<S: Storage>(storage: &mut S) -> Singleton<S, State> {
+// Immutable record of a finalized round, written once by DistributeFunds.
+#[derive(Serialize, Deserialize, Clone, PartialEq, JsonSchema, Debug)]
+pub struct DistributionSnapshot {
+    pub height: u64,
+    pub time: u64,
+    pub distributions: Vec<Distribution>,
+    // Matching left over after rounding, including any pool nothing was matched to.
+    pub dust: Vec<Coin>,
+    // Unreleased milestones taken back from recipients after distribution.
+    pub clawed_back: Vec<Coin>,
+    // Where the dust and anything clawed back was sent under the round's leftover
+    // policy.
+    pub leftover: Vec<LeftoverPayment>,
+    // Leftovers waiting for SendRollover to move them to the successor round.
+    pub pending_rollover: Vec<Coin>,
+}
+
+#[derive(Serialize, Deserialize, Clone, PartialEq, JsonSchema, Debug)]
+pub struct LeftoverPayment {
+    pub recipient: HumanAddr,
+    pub amount: Vec<Coin>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum ClaimStatus {
+    Unclaimed,
+    Claimed,
+    // Sent back to the owner after the claim deadline.
+    Returned,
+}
+
+// Amount a proposal's recipient can claim once funds are distributed.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct Claim {
+    pub proposal: u32,
+    pub recipient: CanonicalAddr,
+    pub amount: Vec<Coin>,
+    // Paid out so far. Only less than `amount` while the distribution is vesting.
+    pub withdrawn: Vec<Coin>,
+    pub status: ClaimStatus,
+    // Escrowed stages of `amount`, copied from the proposal's milestones.
+    pub milestones: Vec<ClaimMilestone>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum MilestoneStatus {
+    Pending,
+    Released,
+    // Returned to the owner because the project was abandoned.
+    ClawedBack,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct ClaimMilestone {
+    pub share: Decimal,
+    pub status: MilestoneStatus,
+}
+
+pub fn config<S: Storage>(storage: &mut S) -> Singleton<'_, S, State> {
     singleton(storage, CONFIG_KEY)
 }
 
-pub fn config_read<S: Storage>(storage: &S) -> ReadonlySingleton<S, State> {
+pub fn config_read<S: Storage>(storage: &S) -> ReadonlySingleton<'_, S, State> {
     singleton_read(storage, CONFIG_KEY)
 }
+
+pub fn distribution_snapshot<S: Storage>(
+    storage: &mut S,
+) -> Singleton<'_, S, DistributionSnapshot> {
+    singleton(storage, DISTRIBUTION_KEY)
+}
+
+pub fn distribution_snapshot_read<S: ReadonlyStorage>(
+    storage: &S,
+) -> ReadonlySingleton<'_, S, DistributionSnapshot> {
+    singleton_read(storage, DISTRIBUTION_KEY)
+}
+
+// Proposals are keyed by their big-endian id so range queries return them in order.
+pub fn proposals<S: Storage>(storage: &mut S) -> Bucket<'_, S, Proposal> {
+    bucket(storage, PROPOSAL_KEY)
+}
+
+pub fn proposals_read<S: ReadonlyStorage>(storage: &S) -> ReadonlyBucket<'_, S, Proposal> {
+    bucket_read(storage, PROPOSAL_KEY)
+}
+
+// Holds the number of proposals created so far, which is also the next proposal id.
+pub fn proposal_seq<S: Storage>(storage: &mut S) -> Singleton<'_, S, u64> {
+    sequence(storage, PROPOSAL_SEQ_KEY)
+}
+
+pub fn proposal_seq_read<S: ReadonlyStorage>(storage: &S) -> ReadonlySingleton<'_, S, u64> {
+    singleton_read(storage, PROPOSAL_SEQ_KEY)
+}
+
+// Votes are stored under the proposal id, keyed by voter, so a voter has a single
+// accumulated Vote per proposal.
+pub fn votes<S: Storage>(storage: &mut S, proposal_id: u32) -> Bucket<'_, S, Vote> {
+    Bucket::multilevel(storage, &[VOTE_KEY, &proposal_id.to_be_bytes()])
+}
+
+pub fn votes_read<S: ReadonlyStorage>(
+    storage: &S,
+    proposal_id: u32,
+) -> ReadonlyBucket<'_, S, Vote> {
+    ReadonlyBucket::multilevel(storage, &[VOTE_KEY, &proposal_id.to_be_bytes()])
+}
+
+// Index of the proposal ids a voter has voted on, keyed by voter then proposal id.
+pub fn voter_index<'a, S: Storage>(
+    storage: &'a mut S,
+    voter: &CanonicalAddr,
+) -> Bucket<'a, S, u32> {
+    Bucket::multilevel(storage, &[VOTER_INDEX_KEY, voter.as_slice()])
+}
+
+pub fn voter_index_read<'a, S: ReadonlyStorage>(
+    storage: &'a S,
+    voter: &CanonicalAddr,
+) -> ReadonlyBucket<'a, S, u32> {
+    ReadonlyBucket::multilevel(storage, &[VOTER_INDEX_KEY, voter.as_slice()])
+}
+
+// Matching pool deposits, keyed by sponsor.
+pub fn sponsors<S: Storage>(storage: &mut S) -> Bucket<'_, S, Vec<Coin>> {
+    bucket(storage, SPONSOR_KEY)
+}
+
+pub fn sponsors_read<S: ReadonlyStorage>(storage: &S) -> ReadonlyBucket<'_, S, Vec<Coin>> {
+    bucket_read(storage, SPONSOR_KEY)
+}
+
+// Total of every sponsor deposit. This, not the contract balance, is the matching pool.
+pub fn pool<S: Storage>(storage: &mut S) -> Singleton<'_, S, Vec<Coin>> {
+    singleton(storage, POOL_KEY)
+}
+
+pub fn pool_read<S: ReadonlyStorage>(storage: &S) -> ReadonlySingleton<'_, S, Vec<Coin>> {
+    singleton_read(storage, POOL_KEY)
+}
+
+// Refunds paid out after a round was cancelled, keyed by recipient.
+pub fn refunds<S: Storage>(storage: &mut S) -> Bucket<'_, S, Vec<Coin>> {
+    bucket(storage, REFUND_KEY)
+}
+
+pub fn refunds_read<S: ReadonlyStorage>(storage: &S) -> ReadonlyBucket<'_, S, Vec<Coin>> {
+    bucket_read(storage, REFUND_KEY)
+}
+
+// Claims are keyed by the big-endian proposal id.
+pub fn claims<S: Storage>(storage: &mut S) -> Bucket<'_, S, Claim> {
+    bucket(storage, CLAIM_KEY)
+}
+
+pub fn claims_read<S: ReadonlyStorage>(storage: &S) -> ReadonlyBucket<'_, S, Claim> {
+    bucket_read(storage, CLAIM_KEY)
+}
+
+pub fn load_proposals<S: ReadonlyStorage>(storage: &S) -> StdResult<Vec<Proposal>> {
+    proposals_read(storage)
+        .range(None, None, Order::Ascending)
+        .map(|item| item.map(|(_, proposal)| proposal))
+        .collect()
+}
+
+// Proposals that can be voted on and matched.
+pub fn load_approved_proposals<S: ReadonlyStorage>(storage: &S) -> StdResult<Vec<Proposal>> {
+    Ok(load_proposals(storage)?
+        .into_iter()
+        .filter(|proposal| proposal.approved)
+        .collect())
+}
+
+pub fn load_votes<S: ReadonlyStorage>(storage: &S, proposal_id: u32) -> StdResult<Vec<Vote>> {
+    votes_read(storage, proposal_id)
+        .range(None, None, Order::Ascending)
+        .map(|item| item.map(|(_, vote)| vote))
+        .collect()
+}
+
+pub fn load_all_votes<S: ReadonlyStorage>(storage: &S) -> StdResult<Vec<Vote>> {
+    let mut all_votes = Vec::new();
+    for proposal in load_proposals(storage)? {
+        all_votes.extend(load_votes(storage, proposal.id)?);
+    }
+    Ok(all_votes)
+}
@@ -1,16 +1,35 @@
 #[cfg(test)]
+#[allow(clippy::module_inception)]
 mod tests {
-    use crate::contract::{calculate_distributions, get_normalized_votes, handle, init, query};
+    use crate::contract::{
+        add_coins, calculate_distributions, calculate_dust, check_conservation,
+        get_normalized_votes, handle, init, query, total_contributions,
+    };
     use crate::error::ContractError;
+    use crate::group::{Cw4QueryMsg, Group, MemberResponse};
+    use crate::math::Fixed;
+    use crate::merkle;
     use crate::msg::{
-        CheckDistributionsResponse, CreateProposalResponse, HandleMsg, InitMsg,
-        ProposalListResponse, ProposalStateResponse, QueryMsg, StateResponse,
+        CheckDistributionsResponse, ClaimsResponse, CreateProposalResponse,
+        FinalDistributionsResponse, HandleMsg, HypotheticalVote, InitMsg, PhaseResponse,
+        ProposalListResponse, ProposalStateResponse, QueryMsg, RolesResponse, SponsorsResponse,
+        StateResponse, VestingResponse, VoterProof, VotesByProposalResponse, VotesByVoterResponse,
+    };
+    use crate::phase::{Expiration, PhaseDeadline, RoundPhase};
+    use crate::state::{
+        load_all_votes, load_proposals, ClaimStatus, Distribution, DistributionSnapshot,
+        FundingFormula, LeftoverPayment, LeftoverPolicy, Milestone, Proposal, Role, Vote,
     };
-    use crate::state::{config_read, Distribution, Proposal, Vote};
+    use crate::vesting::VestingSchedule;
     use cosmwasm_std::testing::{
         mock_dependencies, mock_env, mock_info, MockApi, MockQuerier, MockStorage,
     };
-    use cosmwasm_std::{coins, from_binary, Api, BankMsg, Coin, CosmosMsg, Extern, HumanAddr};
+    use cosmwasm_std::{
+        attr, coin, coins, from_binary, from_slice, to_binary, Api, BankMsg, Coin, ContractResult,
+        CosmosMsg, Decimal, Empty, Extern, HandleResponse, HumanAddr, Querier, QuerierResult,
+        QueryRequest, StdError, SystemError, SystemResult, Uint128, WasmMsg, WasmQuery,
+    };
+    use std::collections::BTreeMap;
 
     fn default_init_msg() -> InitMsg {
         let env = mock_env();
@@ -26,10 +45,24 @@ mod tests {
                 HumanAddr::from("voter_1"),
                 HumanAddr::from("voter_2"),
             ],
-            proposal_period_start: Some(env.block.time),
-            proposal_period_end: Some(env.block.time + 86400),
-            voting_period_start: Some(env.block.time + 86400 * 2),
-            voting_period_end: Some(env.block.time + 86400 * 5),
+            open_proposals: false,
+            open_voting: false,
+            voter_merkle_root: None,
+            group: None,
+            proposal_period_start: Some(Expiration::AtTime(env.block.time)),
+            proposal_period_end: Some(Expiration::AtTime(env.block.time + 86400)),
+            voting_period_start: Some(Expiration::AtTime(env.block.time + 86400 * 2)),
+            voting_period_end: Some(Expiration::AtTime(env.block.time + 86400 * 5)),
+            funding_formula: None,
+            accepted_denoms: vec!["earth".to_string(), "uearth".to_string()],
+            min_contribution: vec![],
+            claim_deadline: None,
+            vesting: None,
+            reviewers: vec![HumanAddr::from("reviewer")],
+            require_approval: false,
+            leftover_policy: None,
+            operators: vec![HumanAddr::from("operator")],
+            treasurers: vec![HumanAddr::from("treasurer")],
         }
     }
 
@@ -46,16 +79,39 @@ mod tests {
                 HumanAddr::from("voter_1"),
                 HumanAddr::from("voter_2"),
             ],
+            open_proposals: false,
+            open_voting: false,
+            voter_merkle_root: None,
+            group: None,
             proposal_period_start: None,
             proposal_period_end: None,
             voting_period_start: None,
             voting_period_end: None,
+            funding_formula: None,
+            accepted_denoms: vec!["earth".to_string(), "uearth".to_string()],
+            min_contribution: vec![],
+            claim_deadline: None,
+            vesting: None,
+            reviewers: vec![HumanAddr::from("reviewer")],
+            require_approval: false,
+            leftover_policy: None,
+            operators: vec![HumanAddr::from("operator")],
+            treasurers: vec![HumanAddr::from("treasurer")],
         }
     }
 
-    fn mock_init(mut deps: &mut Extern<MockStorage, MockApi, MockQuerier>, msg: InitMsg) {
-        let info = mock_info("owner", &coins(1000, "earth"));
-        let _res = init(&mut deps, mock_env(), info, msg).unwrap();
+    fn mock_init(deps: &mut Extern<MockStorage, MockApi, MockQuerier>, msg: InitMsg) {
+        mock_init_with_pool(deps, msg, &coins(1000, "earth"));
+    }
+
+    // Funds sent at instantiation are the owner's deposit into the matching pool.
+    fn mock_init_with_pool(
+        deps: &mut Extern<MockStorage, MockApi, MockQuerier>,
+        msg: InitMsg,
+        pool: &[Coin],
+    ) {
+        let info = mock_info("owner", pool);
+        let _res = init(deps, mock_env(), info, msg).unwrap();
     }
 
     fn default_proposal_msg() -> HandleMsg {
@@ -64,27 +120,114 @@ mod tests {
             recipient: HumanAddr::from("proposal_recipient"),
             description: "The proposal description".to_string(),
             tags: "one two three".to_string(),
+            milestones: None,
         }
     }
 
-    fn mock_proposal(mut deps: &mut Extern<MockStorage, MockApi, MockQuerier>, msg: HandleMsg) {
+    fn mock_proposal(deps: &mut Extern<MockStorage, MockApi, MockQuerier>, msg: HandleMsg) {
         let info = mock_info("proposer_0", &coins(1000, "earth"));
-        let _res = handle(&mut deps, mock_env(), info, msg).unwrap();
+        let _res = handle(deps, mock_env(), info, msg).unwrap();
+    }
+
+    fn assert_invalid_schedule(msg: InitMsg) {
+        let mut deps = mock_dependencies(&[]);
+        let info = mock_info("owner", &coins(1000, "earth"));
+        let res = init(&mut deps, mock_env(), info, msg);
+        match res {
+            Err(ContractError::InvalidSchedule { reason: _ }) => {}
+            _ => panic!("Must return error"),
+        }
     }
 
     #[test]
     fn fails_initialization_invalid_proposal_period() {
-        // TODO: Test that proposal start is before the end.
+        let mut msg = default_init_msg();
+        msg.proposal_period_end = Some(Expiration::AtTime(mock_env().block.time - 1));
+        assert_invalid_schedule(msg);
     }
 
     #[test]
     fn fails_initialization_invalid_voting_period() {
-        // TODO: Test that vote start is before the end.
+        let mut msg = default_init_msg();
+        msg.voting_period_end = Some(Expiration::AtTime(mock_env().block.time + 86400));
+        assert_invalid_schedule(msg);
     }
 
     #[test]
     fn fails_initialization_voting_before_proposal() {
-        // TODO: Test that proposal period ends before the voting period.
+        // voting overlaps the end of the proposal period.
+        let mut msg = default_init_msg();
+        msg.voting_period_start = msg.proposal_period_end;
+        assert_invalid_schedule(msg);
+
+        // voting starts before proposals do.
+        let mut msg = empty_period_init_msg();
+        msg.proposal_period_start = Some(Expiration::AtTime(mock_env().block.time));
+        msg.voting_period_start = Some(Expiration::AtTime(mock_env().block.time - 1));
+        assert_invalid_schedule(msg);
+    }
+
+    #[test]
+    fn fails_initialization_out_of_order_with_gaps() {
+        let t = mock_env().block.time;
+
+        // voting ends before the proposal period does, with no voting start set.
+        let mut msg = empty_period_init_msg();
+        msg.proposal_period_end = Some(Expiration::AtTime(t + 200));
+        msg.voting_period_end = Some(Expiration::AtTime(t + 150));
+        assert_invalid_schedule(msg);
+
+        // claims close before the proposal period ends, with no voting end set.
+        let mut msg = empty_period_init_msg();
+        msg.proposal_period_end = Some(Expiration::AtTime(t + 200));
+        msg.claim_deadline = Some(Expiration::AtTime(t + 100));
+        assert_invalid_schedule(msg);
+
+        // claims close before voting starts.
+        let mut msg = empty_period_init_msg();
+        msg.voting_period_start = Some(Expiration::AtTime(t + 200));
+        msg.claim_deadline = Some(Expiration::AtTime(t + 200));
+        assert_invalid_schedule(msg);
+    }
+
+    #[test]
+    fn fails_period_change_invalid_schedule() {
+        let mut env = mock_env();
+        let mut deps = mock_dependencies(&[]);
+        let info = mock_info("owner", &[]);
+        let mut msg = empty_period_init_msg();
+        msg.voting_period_end = Some(Expiration::AtTime(env.block.time + 1000));
+        let _res = init(&mut deps, env.clone(), info.clone(), msg).unwrap();
+
+        // periods can't be moved into the past.
+        let start = HandleMsg::StartProposalPeriod {
+            at: Some(Expiration::AtTime(env.block.time - 1)),
+        };
+        let res = handle(&mut deps, env.clone(), info.clone(), start);
+        match res {
+            Err(ContractError::InvalidSchedule { reason: _ }) => {}
+            _ => panic!("Must return error"),
+        }
+
+        let start = HandleMsg::StartProposalPeriod { at: None };
+        let _res = handle(&mut deps, env.clone(), info.clone(), start).unwrap();
+        env.block.time += 100;
+        let end = HandleMsg::EndProposalPeriod { at: None };
+        let _res = handle(&mut deps, env.clone(), info.clone(), end).unwrap();
+
+        // voting can't start after its configured end.
+        env.block.time += 100;
+        let start_voting = HandleMsg::StartVotingPeriod {
+            at: Some(Expiration::AtTime(env.block.time + 2000)),
+        };
+        let res = handle(&mut deps, env.clone(), info.clone(), start_voting);
+        match res {
+            Err(ContractError::InvalidSchedule { reason: _ }) => {}
+            _ => panic!("Must return error"),
+        }
+
+        let start_voting = HandleMsg::StartVotingPeriod { at: None };
+        let _res = handle(&mut deps, env, info, start_voting).unwrap();
     }
 
     #[test]
@@ -122,8 +265,8 @@ mod tests {
         let _res = init(&mut deps, env.clone(), info.clone(), msg).unwrap();
 
         // Define start and end messages.
-        let start = HandleMsg::StartProposalPeriod { time: None };
-        let end = HandleMsg::EndProposalPeriod { time: None };
+        let start = HandleMsg::StartProposalPeriod { at: None };
+        let end = HandleMsg::EndProposalPeriod { at: None };
 
         // Try to start as any user.
         let res = handle(
@@ -177,8 +320,8 @@ mod tests {
         let _res = init(&mut deps, env.clone(), info.clone(), msg).unwrap();
 
         // Define start and end messages.
-        let start_voting = HandleMsg::StartVotingPeriod { time: None };
-        let end_voting = HandleMsg::EndVotingPeriod { time: None };
+        let start_voting = HandleMsg::StartVotingPeriod { at: None };
+        let end_voting = HandleMsg::EndVotingPeriod { at: None };
 
         // Try to start as any user.
         let res = handle(
@@ -201,7 +344,7 @@ mod tests {
         }
 
         // Start proposal period.
-        let start_proposal = HandleMsg::StartProposalPeriod { time: None };
+        let start_proposal = HandleMsg::StartProposalPeriod { at: None };
         let _res = handle(&mut deps, env.clone(), info.clone(), start_proposal).unwrap();
 
         // Try to start voting before proposal end.
@@ -213,7 +356,7 @@ mod tests {
         }
 
         // End the proposal period.
-        let end_proposal = HandleMsg::EndProposalPeriod { time: None };
+        let end_proposal = HandleMsg::EndProposalPeriod { at: None };
         let _res = handle(&mut deps, env.clone(), info.clone(), end_proposal).unwrap();
 
         // Try to end voting before starting.
@@ -265,8 +408,8 @@ mod tests {
         }
 
         // proposal should not have been created.
-        let state = config_read(&deps.storage).load().unwrap();
-        assert_eq!(0, state.proposals.len(),);
+        let proposals = load_proposals(&deps.storage).unwrap();
+        assert_eq!(0, proposals.len(),);
     }
 
     #[test]
@@ -276,8 +419,8 @@ mod tests {
 
         // change proposal time so it has already expired.
         let mut msg = default_init_msg();
-        msg.proposal_period_start = Some(env.block.time - 86400 * 5);
-        msg.proposal_period_end = Some(env.block.time - 86400 * 1);
+        msg.proposal_period_start = Some(Expiration::AtTime(env.block.time - 86400 * 5));
+        msg.proposal_period_end = Some(Expiration::AtTime(env.block.time - 86400));
         mock_init(&mut deps, msg);
 
         // create proposal.
@@ -292,8 +435,8 @@ mod tests {
         }
 
         // proposal should not have been created.
-        let state = config_read(&deps.storage).load().unwrap();
-        assert_eq!(0, state.proposals.len(),);
+        let proposals = load_proposals(&deps.storage).unwrap();
+        assert_eq!(0, proposals.len(),);
     }
 
     #[test]
@@ -305,9 +448,10 @@ mod tests {
     fn create_proposal_no_proposer_list() {
         let mut deps = mock_dependencies(&[]);
 
-        // modify init message to empty proposer whitelist.
+        // modify init message to empty proposer whitelist, open to anyone.
         let mut msg = default_init_msg();
         msg.proposer_whitelist = Vec::new();
+        msg.open_proposals = true;
         mock_init(&mut deps, msg);
 
         // create proposal.
@@ -321,11 +465,19 @@ mod tests {
         assert_eq!(0, value.proposal_id);
 
         // proposal should be created.
-        let state = config_read(&deps.storage).load().unwrap();
-        assert_eq!(1, state.proposals.len(),);
+        let proposals = load_proposals(&deps.storage).unwrap();
+        assert_eq!(1, proposals.len(),);
 
         // test Proposal List query response.
-        let res = query(&mut deps, mock_env(), QueryMsg::ProposalList {}).unwrap();
+        let res = query(
+            &deps,
+            mock_env(),
+            QueryMsg::ProposalList {
+                start_after: None,
+                limit: None,
+            },
+        )
+        .unwrap();
         let value: ProposalListResponse = from_binary(&res).unwrap();
         assert_eq!(1, value.proposals.len());
         assert_eq!("My proposal", value.proposals[0].name);
@@ -337,7 +489,7 @@ mod tests {
 
         // test Proposal State query response.
         let res = query(
-            &mut deps,
+            &deps,
             mock_env(),
             QueryMsg::ProposalState { proposal_id: 0 },
         )
@@ -345,7 +497,6 @@ mod tests {
         let value: ProposalStateResponse = from_binary(&res).unwrap();
         assert_eq!("My proposal", value.proposal.name);
         assert_eq!(recipient, value.proposal.recipient);
-        assert_eq!(0, value.votes.len());
     }
 
     #[test]
@@ -359,6 +510,7 @@ mod tests {
             recipient: HumanAddr::from("proposal_recipient"),
             description: "The proposal description".to_string(),
             tags: "one two three".to_string(),
+            milestones: None,
         };
 
         let info = mock_info("proposer_0", &coins(1000, "earth"));
@@ -368,11 +520,19 @@ mod tests {
         assert_eq!(0, value.proposal_id);
 
         // proposal should be created.
-        let state = config_read(&deps.storage).load().unwrap();
-        assert_eq!(1, state.proposals.len(),);
+        let proposals = load_proposals(&deps.storage).unwrap();
+        assert_eq!(1, proposals.len(),);
 
         // test Proposal List query response.
-        let res = query(&mut deps, mock_env(), QueryMsg::ProposalList {}).unwrap();
+        let res = query(
+            &deps,
+            mock_env(),
+            QueryMsg::ProposalList {
+                start_after: None,
+                limit: None,
+            },
+        )
+        .unwrap();
         let value: ProposalListResponse = from_binary(&res).unwrap();
         assert_eq!(1, value.proposals.len());
         assert_eq!("My proposal", value.proposals[0].name);
@@ -384,7 +544,7 @@ mod tests {
 
         // test Proposal State query response.
         let res = query(
-            &mut deps,
+            &deps,
             mock_env(),
             QueryMsg::ProposalState { proposal_id: 0 },
         )
@@ -392,7 +552,6 @@ mod tests {
         let value: ProposalStateResponse = from_binary(&res).unwrap();
         assert_eq!("My proposal", value.proposal.name);
         assert_eq!(recipient, value.proposal.recipient);
-        assert_eq!(0, value.votes.len());
     }
 
     #[test]
@@ -402,14 +561,17 @@ mod tests {
         mock_proposal(&mut deps, default_proposal_msg());
 
         // create vote.
-        let vote_msg = HandleMsg::CreateVote { proposal_id: 1 };
+        let vote_msg = HandleMsg::CreateVote {
+            proposal_id: 1,
+            proof: None,
+        };
 
         // try to create a vote as "any user"
         let info = mock_info("any_user", &coins(1000, "earth"));
 
         // set the time to the voting period.
         let mut env = mock_env();
-        env.block.time = env.block.time + 86400 * 3;
+        env.block.time += 86400 * 3;
 
         // send message.
         let res = handle(&mut deps, env, info, vote_msg);
@@ -419,8 +581,8 @@ mod tests {
         }
 
         // vote should not be created.
-        let state = config_read(&deps.storage).load().unwrap();
-        assert_eq!(0, state.votes.len(),);
+        let votes = load_all_votes(&deps.storage).unwrap();
+        assert_eq!(0, votes.len(),);
     }
 
     #[test]
@@ -432,12 +594,15 @@ mod tests {
 
         // create vote.
         // use an invalid proposal id.
-        let vote_msg = HandleMsg::CreateVote { proposal_id: 2 };
+        let vote_msg = HandleMsg::CreateVote {
+            proposal_id: 2,
+            proof: None,
+        };
         let info = mock_info("voter_0", &coins(1000, "earth"));
 
         // set the time to the voting period.
         let mut env = mock_env();
-        env.block.time = env.block.time + 86400 * 3;
+        env.block.time += 86400 * 3;
 
         // send message.
         let res = handle(&mut deps, env, info, vote_msg);
@@ -447,8 +612,8 @@ mod tests {
         }
 
         // vote should not be created.
-        let state = config_read(&deps.storage).load().unwrap();
-        assert_eq!(0, state.votes.len(),);
+        let votes = load_all_votes(&deps.storage).unwrap();
+        assert_eq!(0, votes.len(),);
     }
 
     #[test]
@@ -458,12 +623,15 @@ mod tests {
         mock_proposal(&mut deps, default_proposal_msg());
 
         // create vote.
-        let vote_msg = HandleMsg::CreateVote { proposal_id: 1 };
+        let vote_msg = HandleMsg::CreateVote {
+            proposal_id: 1,
+            proof: None,
+        };
         let info = mock_info("voter_0", &coins(1000, "earth"));
 
         // set the time to the proposal period.
         let mut env = mock_env();
-        env.block.time = env.block.time + 86400 * 1;
+        env.block.time += 86400;
 
         // send message.
         let res = handle(&mut deps, env, info, vote_msg);
@@ -473,60 +641,65 @@ mod tests {
         }
 
         // vote should not be created.
-        let state = config_read(&deps.storage).load().unwrap();
-        assert_eq!(0, state.votes.len(),);
+        let votes = load_all_votes(&deps.storage).unwrap();
+        assert_eq!(0, votes.len(),);
     }
 
     #[test]
     fn create_vote_no_voter_list() {
         let mut deps = mock_dependencies(&[]);
 
-        // modify init message to empty voter whitelist.
+        // modify init message to empty voter whitelist, open to anyone.
         let mut msg = default_init_msg();
         msg.voter_whitelist = Vec::new();
+        msg.open_voting = true;
         mock_init(&mut deps, msg);
 
         mock_proposal(&mut deps, default_proposal_msg());
 
         // create vote.
-        let vote_msg = HandleMsg::CreateVote { proposal_id: 0 };
+        let vote_msg = HandleMsg::CreateVote {
+            proposal_id: 0,
+            proof: None,
+        };
 
         // try to create a vote as "any user"
         let info = mock_info("any_user", &coins(1000, "earth"));
 
         // set the time to the voting period.
         let mut env = mock_env();
-        env.block.time = env.block.time + 86400 * 3;
+        env.block.time += 86400 * 3;
 
         // send message.
         let _res = handle(&mut deps, env, info, vote_msg).unwrap();
 
         // vote should be created.
-        let state = config_read(&deps.storage).load().unwrap();
-        assert_eq!(1, state.votes.len(),);
+        let votes = load_all_votes(&deps.storage).unwrap();
+        assert_eq!(1, votes.len(),);
 
         // check voter address.
         let voter = deps
             .api
             .canonical_address(&HumanAddr("any_user".to_string()))
             .unwrap();
-        assert_eq!(voter, state.votes[0].voter);
+        assert_eq!(voter, votes[0].voter);
 
         // check amount.
-        assert_eq!(coins(1000, "earth"), state.votes[0].amount);
+        assert_eq!(coins(1000, "earth"), votes[0].amount);
 
         // test Proposal State query response.
         let res = query(
-            &mut deps,
+            &deps,
             mock_env(),
             QueryMsg::ProposalState { proposal_id: 0 },
         )
         .unwrap();
         let value: ProposalStateResponse = from_binary(&res).unwrap();
         assert_eq!("My proposal", value.proposal.name);
-        assert_eq!(1, value.votes.len());
-        assert_eq!(voter, value.votes[0].voter);
-        assert_eq!(coins(1000, "earth"), value.votes[0].amount);
+        let votes = query_proposal_votes(&deps, 0);
+        assert_eq!(1, votes.len());
+        assert_eq!(voter, votes[0].voter);
+        assert_eq!(coins(1000, "earth"), votes[0].amount);
     }
 
     #[test]
@@ -538,60 +711,331 @@ mod tests {
         mock_proposal(&mut deps, default_proposal_msg());
 
         // create vote.
-        let vote_msg = HandleMsg::CreateVote { proposal_id: 2 };
+        let vote_msg = HandleMsg::CreateVote {
+            proposal_id: 2,
+            proof: None,
+        };
         let info = mock_info("voter_0", &coins(1000, "earth"));
 
         // set the time to the voting period.
         let mut env = mock_env();
-        env.block.time = env.block.time + 86400 * 3;
+        env.block.time += 86400 * 3;
 
         // send message.
         let _res = handle(&mut deps, env, info, vote_msg).unwrap();
 
         // vote should be created.
-        let state = config_read(&deps.storage).load().unwrap();
-        assert_eq!(1, state.votes.len(),);
+        let votes = load_all_votes(&deps.storage).unwrap();
+        assert_eq!(1, votes.len(),);
 
         // check voter address.
         let voter = deps
             .api
             .canonical_address(&HumanAddr("voter_0".to_string()))
             .unwrap();
-        assert_eq!(voter, state.votes[0].voter);
+        assert_eq!(voter, votes[0].voter);
 
         // check amount.
-        assert_eq!(coins(1000, "earth"), state.votes[0].amount);
+        assert_eq!(coins(1000, "earth"), votes[0].amount);
 
         // test Proposal State query response.
         let res = query(
-            &mut deps,
+            &deps,
             mock_env(),
             QueryMsg::ProposalState { proposal_id: 2 },
         )
         .unwrap();
         let value: ProposalStateResponse = from_binary(&res).unwrap();
         assert_eq!("My proposal", value.proposal.name);
+        let votes = query_proposal_votes(&deps, 2);
+        assert_eq!(1, votes.len());
+        assert_eq!(voter, votes[0].voter);
+        assert_eq!(coins(1000, "earth"), votes[0].amount);
+    }
+
+    #[test]
+    fn create_vote_accumulates_repeat_votes() {
+        let mut deps = mock_dependencies(&[]);
+        mock_init(&mut deps, default_init_msg());
+        mock_proposal(&mut deps, default_proposal_msg());
+        mock_proposal(&mut deps, default_proposal_msg());
+        mock_vote(&mut deps, "voter_0".to_string(), 0, coins(1000, "earth"));
+        mock_vote(&mut deps, "voter_0".to_string(), 0, coins(500, "earth"));
+        mock_vote(&mut deps, "voter_0".to_string(), 1, coins(200, "earth"));
+
+        // repeat votes on the same proposal are stored as a single vote.
+        let votes = load_all_votes(&deps.storage).unwrap();
+        assert_eq!(2, votes.len());
+
+        let votes = query_proposal_votes(&deps, 0);
+        assert_eq!(1, votes.len());
+        assert_eq!(coins(1500, "earth"), votes[0].amount);
+    }
+
+    #[test]
+    fn fail_create_vote_wrong_denom() {
+        let mut deps = mock_dependencies(&[]);
+        mock_init(&mut deps, default_init_msg());
+        mock_proposal(&mut deps, default_proposal_msg());
+
+        let mut env = mock_env();
+        env.block.time += 86400 * 3;
+        let info = mock_info("voter_0", &coins(1000, "shell"));
+        let msg = HandleMsg::CreateVote {
+            proposal_id: 0,
+            proof: None,
+        };
+        let res = handle(&mut deps, env, info, msg);
+        match res {
+            Err(ContractError::WrongDenom { denom }) => assert_eq!("shell", denom),
+            _ => panic!("Must return error"),
+        }
+    }
+
+    #[test]
+    fn fail_create_vote_multiple_denoms() {
+        let mut deps = mock_dependencies(&[]);
+        mock_init(&mut deps, default_init_msg());
+        mock_proposal(&mut deps, default_proposal_msg());
+
+        let mut env = mock_env();
+        env.block.time += 86400 * 3;
+        let info = mock_info("voter_0", &[coin(1000, "earth"), coin(1000, "uearth")]);
+        let msg = HandleMsg::CreateVote {
+            proposal_id: 0,
+            proof: None,
+        };
+        let res = handle(&mut deps, env, info, msg);
+        match res {
+            Err(ContractError::MultipleDenoms {}) => {}
+            _ => panic!("Must return error"),
+        }
+    }
+
+    #[test]
+    fn fail_create_vote_no_funds() {
+        let mut deps = mock_dependencies(&[]);
+        mock_init(&mut deps, default_init_msg());
+        mock_proposal(&mut deps, default_proposal_msg());
+
+        let mut env = mock_env();
+        env.block.time += 86400 * 3;
+        let msg = HandleMsg::CreateVote {
+            proposal_id: 0,
+            proof: None,
+        };
+        let res = handle(
+            &mut deps,
+            env.clone(),
+            mock_info("voter_0", &[]),
+            msg.clone(),
+        );
+        match res {
+            Err(ContractError::NoFunds {}) => {}
+            _ => panic!("Must return error"),
+        }
+
+        let info = mock_info("voter_0", &coins(0, "earth"));
+        let res = handle(&mut deps, env, info, msg);
+        match res {
+            Err(ContractError::NoFunds {}) => {}
+            _ => panic!("Must return error"),
+        }
+    }
+
+    #[test]
+    fn fail_create_vote_below_minimum() {
+        let mut deps = mock_dependencies(&[]);
+        let mut msg = default_init_msg();
+        msg.min_contribution = coins(100, "earth");
+        mock_init(&mut deps, msg);
+        mock_proposal(&mut deps, default_proposal_msg());
+
+        let mut env = mock_env();
+        env.block.time += 86400 * 3;
+        let msg = HandleMsg::CreateVote {
+            proposal_id: 0,
+            proof: None,
+        };
+        let info = mock_info("voter_0", &coins(99, "earth"));
+        let res = handle(&mut deps, env, info, msg);
+        match res {
+            Err(ContractError::BelowMinimum { minimum, denom }) => {
+                assert_eq!(Uint128(100), minimum);
+                assert_eq!("earth", denom);
+            }
+            _ => panic!("Must return error"),
+        }
+
+        // the minimum is inclusive and only applies to its own denom.
+        mock_vote(&mut deps, "voter_0".to_string(), 0, coins(100, "earth"));
+        mock_vote(&mut deps, "voter_1".to_string(), 0, coins(1, "uearth"));
+    }
+
+    #[test]
+    fn fail_init_minimum_in_unaccepted_denom() {
+        let mut deps = mock_dependencies(&[]);
+        let mut msg = default_init_msg();
+        msg.min_contribution = coins(100, "shell");
+        let res = init(&mut deps, mock_env(), mock_info("owner", &[]), msg);
+        match res {
+            Err(ContractError::WrongDenom { denom }) => assert_eq!("shell", denom),
+            _ => panic!("Must return error"),
+        }
+    }
+
+    #[test]
+    fn fail_init_pool_in_unaccepted_denom() {
+        let mut deps = mock_dependencies(&[]);
+        let info = mock_info("owner", &[coin(1000, "earth"), coin(500, "shell")]);
+        let res = init(&mut deps, mock_env(), info, default_init_msg());
+        match res {
+            Err(ContractError::WrongDenom { denom }) => assert_eq!("shell", denom),
+            _ => panic!("Must return error"),
+        }
+    }
+
+    #[test]
+    fn paginated_proposal_list() {
+        let mut deps = mock_dependencies(&[]);
+        mock_init(&mut deps, default_init_msg());
+        for _ in 0..5 {
+            mock_proposal(&mut deps, default_proposal_msg());
+        }
+
+        // first page.
+        let msg = QueryMsg::ProposalList {
+            start_after: None,
+            limit: Some(2),
+        };
+        let res = query(&deps, mock_env(), msg).unwrap();
+        let value: ProposalListResponse = from_binary(&res).unwrap();
+        let ids: Vec<u32> = value.proposals.iter().map(|p| p.id).collect();
+        assert_eq!(vec![0, 1], ids);
+        assert_eq!(Some(1), value.next_start_after);
+
+        // second page starts after the cursor.
+        let msg = QueryMsg::ProposalList {
+            start_after: value.next_start_after,
+            limit: Some(2),
+        };
+        let res = query(&deps, mock_env(), msg).unwrap();
+        let value: ProposalListResponse = from_binary(&res).unwrap();
+        let ids: Vec<u32> = value.proposals.iter().map(|p| p.id).collect();
+        assert_eq!(vec![2, 3], ids);
+
+        // last page has no cursor.
+        let msg = QueryMsg::ProposalList {
+            start_after: value.next_start_after,
+            limit: Some(2),
+        };
+        let res = query(&deps, mock_env(), msg).unwrap();
+        let value: ProposalListResponse = from_binary(&res).unwrap();
+        let ids: Vec<u32> = value.proposals.iter().map(|p| p.id).collect();
+        assert_eq!(vec![4], ids);
+        assert_eq!(None, value.next_start_after);
+
+        // a full page is only followed by a cursor if there is more.
+        let msg = QueryMsg::ProposalList {
+            start_after: None,
+            limit: Some(5),
+        };
+        let res = query(&deps, mock_env(), msg).unwrap();
+        let value: ProposalListResponse = from_binary(&res).unwrap();
+        assert_eq!(5, value.proposals.len());
+        assert_eq!(None, value.next_start_after);
+    }
+
+    fn query_proposal_votes(
+        deps: &Extern<MockStorage, MockApi, MockQuerier>,
+        proposal_id: u32,
+    ) -> Vec<Vote> {
+        let msg = QueryMsg::VotesByProposal {
+            proposal_id,
+            start_after: None,
+            limit: None,
+        };
+        let res = query(deps, mock_env(), msg).unwrap();
+        let value: VotesByProposalResponse = from_binary(&res).unwrap();
+        value.votes
+    }
+
+    #[test]
+    fn paginated_votes_by_proposal_and_voter() {
+        let mut deps = mock_dependencies(&[]);
+        mock_init(&mut deps, default_init_msg());
+        mock_proposal(&mut deps, default_proposal_msg());
+        mock_proposal(&mut deps, default_proposal_msg());
+        mock_proposal(&mut deps, default_proposal_msg());
+        mock_vote(&mut deps, "voter_0".to_string(), 0, coins(1, "earth"));
+        mock_vote(&mut deps, "voter_1".to_string(), 0, coins(2, "earth"));
+        mock_vote(&mut deps, "voter_2".to_string(), 0, coins(3, "earth"));
+        mock_vote(&mut deps, "voter_0".to_string(), 2, coins(4, "earth"));
+
+        // votes on proposal 0, two at a time.
+        let msg = QueryMsg::VotesByProposal {
+            proposal_id: 0,
+            start_after: None,
+            limit: Some(2),
+        };
+        let res = query(&deps, mock_env(), msg).unwrap();
+        let value: VotesByProposalResponse = from_binary(&res).unwrap();
+        assert_eq!(2, value.votes.len());
+        let cursor = value.next_start_after.unwrap();
+
+        let msg = QueryMsg::VotesByProposal {
+            proposal_id: 0,
+            start_after: Some(cursor),
+            limit: Some(2),
+        };
+        let res = query(&deps, mock_env(), msg).unwrap();
+        let value: VotesByProposalResponse = from_binary(&res).unwrap();
+        assert_eq!(1, value.votes.len());
+        assert_eq!(None, value.next_start_after);
+
+        // votes cast by voter_0.
+        let msg = QueryMsg::VotesByVoter {
+            voter: HumanAddr::from("voter_0"),
+            start_after: None,
+            limit: None,
+        };
+        let res = query(&deps, mock_env(), msg).unwrap();
+        let value: VotesByVoterResponse = from_binary(&res).unwrap();
+        let proposal_ids: Vec<u32> = value.votes.iter().map(|v| v.proposal).collect();
+        assert_eq!(vec![0, 2], proposal_ids);
+        assert_eq!(coins(4, "earth"), value.votes[1].amount);
+        assert_eq!(None, value.next_start_after);
+
+        let msg = QueryMsg::VotesByVoter {
+            voter: HumanAddr::from("voter_0"),
+            start_after: Some(0),
+            limit: None,
+        };
+        let res = query(&deps, mock_env(), msg).unwrap();
+        let value: VotesByVoterResponse = from_binary(&res).unwrap();
         assert_eq!(1, value.votes.len());
-        assert_eq!(voter, value.votes[0].voter);
-        assert_eq!(coins(1000, "earth"), value.votes[0].amount);
+        assert_eq!(2, value.votes[0].proposal);
     }
 
     fn mock_vote(
-        mut deps: &mut Extern<MockStorage, MockApi, MockQuerier>,
+        deps: &mut Extern<MockStorage, MockApi, MockQuerier>,
         voter: String,
         proposal_id: u32,
         amount: Vec<Coin>,
     ) {
         // create vote.
-        let vote_msg = HandleMsg::CreateVote { proposal_id };
+        let vote_msg = HandleMsg::CreateVote {
+            proposal_id,
+            proof: None,
+        };
         let info = mock_info(voter, &amount);
 
         // set the time to the voting period.
         let mut env = mock_env();
-        env.block.time = env.block.time + 86400 * 3;
+        env.block.time += 86400 * 3;
 
         // send message.
-        let _res = handle(&mut deps, env, info, vote_msg).unwrap();
+        let _res = handle(deps, env, info, vote_msg).unwrap();
     }
 
     #[test]
@@ -654,8 +1098,8 @@ mod tests {
             .filter(|x| &*x.1 == "voter_1")
             .collect();
         assert_eq!(votes_by_voter_1.len(), 1);
-        assert_eq!(votes_by_voter_1[0].2, 1_000_000);
-        assert_eq!(votes_by_voter_1[0].3, "uearth");
+        assert_eq!(votes_by_voter_1[0].2, 1);
+        assert_eq!(votes_by_voter_1[0].3, "earth");
         // println!("{:?}", votes_by_voter_1);
         // assert_eq!(votes_by_voter_1[0].2, 1_000_000);
 
@@ -664,8 +1108,8 @@ mod tests {
             .filter(|x| &*x.1 == "voter_0" && x.0 == 0)
             .collect();
         assert_eq!(votes_for_prop_0_by_voter_0.len(), 1);
-        assert_eq!(votes_for_prop_0_by_voter_0[0].2, 2_000_000);
-        assert_eq!(votes_for_prop_0_by_voter_0[0].3, "uearth");
+        assert_eq!(votes_for_prop_0_by_voter_0[0].2, 2);
+        assert_eq!(votes_for_prop_0_by_voter_0[0].3, "earth");
 
         // println!("{:#?}", pretty_result);
     }
@@ -684,7 +1128,7 @@ mod tests {
         // set the time to the proposal period.
         // cannot check distributions during proposal period.
         let mut env = mock_env();
-        env.block.time = env.block.time + 86400 * 1;
+        env.block.time += 86400;
 
         // send message.
         let msg = HandleMsg::CheckDistributions {};
@@ -713,7 +1157,7 @@ mod tests {
 
         // set the time to the voting period.
         let mut env = mock_env();
-        env.block.time = env.block.time + 86400 * 3;
+        env.block.time += 86400 * 3;
 
         // send message.
         let msg = HandleMsg::CheckDistributions {};
@@ -723,14 +1167,14 @@ mod tests {
 
         // println!("{:?}", value);
         // assert there is a ProposalDistribution for every proposal.
-        let state = config_read(&deps.storage).load().unwrap();
-        assert_eq!(state.proposals.len(), value.distributions.len());
+        let proposals = load_proposals(&deps.storage).unwrap();
+        assert_eq!(proposals.len(), value.distributions.len());
     }
 
     #[test]
     fn check_distributions2() {
         let mut deps = mock_dependencies(&coins(10000, "uearth"));
-        mock_init(&mut deps, default_init_msg());
+        mock_init_with_pool(&mut deps, default_init_msg(), &coins(10000, "uearth"));
         mock_proposal(&mut deps, default_proposal_msg());
         mock_proposal(&mut deps, default_proposal_msg());
         // mock_proposal(&mut deps, default_proposal_msg());
@@ -745,7 +1189,7 @@ mod tests {
 
         // set the time to the voting period.
         let mut env = mock_env();
-        env.block.time = env.block.time + 86400 * 3;
+        env.block.time += 86400 * 3;
 
         // send message.
         let msg = HandleMsg::CheckDistributions {};
@@ -760,12 +1204,70 @@ mod tests {
             .sum();
         println!("total dists {}", total_dists);
         // assert there is a ProposalDistribution for every proposal.
-        let state = config_read(&deps.storage).load().unwrap();
-        assert_eq!(state.proposals.len(), value.distributions.len());
+        let proposals = load_proposals(&deps.storage).unwrap();
+        assert_eq!(proposals.len(), value.distributions.len());
         // println!("{:?}", deps.querier.query_all_balances(env.contract.address));
         // println!("{:?}", deps.querier.query_all_balances(env.contract.address));
     }
 
+    #[test]
+    fn query_distributions() {
+        let mut deps = mock_dependencies(&coins(10000, "uearth"));
+        mock_init_with_pool(&mut deps, default_init_msg(), &coins(10000, "uearth"));
+        mock_proposal(&mut deps, default_proposal_msg());
+        mock_proposal(&mut deps, default_proposal_msg());
+        mock_vote(&mut deps, "voter_0".to_string(), 0, coins(1000, "uearth"));
+        mock_vote(&mut deps, "voter_1".to_string(), 0, coins(4000, "uearth"));
+        mock_vote(&mut deps, "voter_2".to_string(), 1, coins(9000, "uearth"));
+        mock_vote(&mut deps, "voter_0".to_string(), 1, coins(16000, "uearth"));
+
+        let mut env = mock_env();
+        env.block.time += 86400 * 3;
+
+        // query matches the CheckDistributions transaction.
+        let msg = QueryMsg::Distributions {
+            hypothetical_vote: None,
+        };
+        let res = query(&deps, env.clone(), msg).unwrap();
+        let value: CheckDistributionsResponse = from_binary(&res).unwrap();
+        let info = mock_info("any_user", &[]);
+        let res = handle(
+            &mut deps,
+            env.clone(),
+            info,
+            HandleMsg::CheckDistributions {},
+        )
+        .unwrap();
+        let checked: CheckDistributionsResponse = from_binary(&res.data.unwrap()).unwrap();
+        assert_eq!(checked, value);
+
+        // a hypothetical vote for proposal 0 increases its distribution.
+        let msg = QueryMsg::Distributions {
+            hypothetical_vote: Some(HypotheticalVote {
+                voter: HumanAddr::from("voter_2"),
+                proposal_id: 0,
+                amount: coin(4000, "uearth"),
+            }),
+        };
+        let res = query(&deps, env.clone(), msg).unwrap();
+        let what_if: CheckDistributionsResponse = from_binary(&res).unwrap();
+        assert_eq!(3, what_if.distributions[0].votes.len());
+        assert!(
+            what_if.distributions[0].distribution_actual.amount
+                > value.distributions[0].distribution_actual.amount
+        );
+
+        // hypothetical votes must reference an existing proposal.
+        let msg = QueryMsg::Distributions {
+            hypothetical_vote: Some(HypotheticalVote {
+                voter: HumanAddr::from("voter_2"),
+                proposal_id: 5,
+                amount: coin(4000, "uearth"),
+            }),
+        };
+        assert!(query(&deps, env, msg).is_err());
+    }
+
     #[test]
     fn fail_distribute_funds_unauthorized() {
         let mut deps = mock_dependencies(&[]);
@@ -777,7 +1279,7 @@ mod tests {
         // set the time to the voting period.
         // cannot check distributions during voting period.
         let mut env = mock_env();
-        env.block.time = env.block.time + 86400 * 6;
+        env.block.time += 86400 * 6;
 
         // send message.
         let msg = HandleMsg::DistributeFunds {};
@@ -799,7 +1301,7 @@ mod tests {
         // set the time to the voting period.
         // cannot check distributions during voting period.
         let mut env = mock_env();
-        env.block.time = env.block.time + 86400 * 3;
+        env.block.time += 86400 * 3;
 
         // send message.
         let msg = HandleMsg::DistributeFunds {};
@@ -813,8 +1315,7 @@ mod tests {
     #[test]
     fn distribute_funds() {
         let mut deps = mock_dependencies(&coins(10000, "uearth"));
-        let info = mock_info("owner", &[]);
-        let _res = init(&mut deps, mock_env(), info, default_init_msg()).unwrap();
+        mock_init_with_pool(&mut deps, default_init_msg(), &coins(10000, "uearth"));
 
         mock_proposal(&mut deps, default_proposal_msg());
         mock_proposal(&mut deps, default_proposal_msg());
@@ -828,32 +1329,68 @@ mod tests {
 
         // set the time to after the voting period.
         let mut env = mock_env();
-        env.block.time = env.block.time + 86400 * 6;
+        env.block.time += 86400 * 6;
 
         // send message.
         let msg = HandleMsg::DistributeFunds {};
-        let res = handle(&mut deps, env, info, msg).unwrap();
+        let _res = handle(&mut deps, env, info, msg).unwrap();
 
-        // assert there is a Distribution for every proposal.
-        let state = config_read(&deps.storage).load().unwrap();
-        assert_eq!(state.proposals.len(), res.messages.len(),);
-        // TODO: Assert that proposal recipients got funds.
+        // assert there is a claim for every proposal.
+        let proposals = load_proposals(&deps.storage).unwrap();
+        let claims = claimable_amounts(&deps);
+        assert_eq!(proposals.len(), claims.len());
 
-        let amounts: Vec<u128> = res
-            .messages
+        let amounts: Vec<u128> = claims
             .iter()
-            .map(|x| match x {
-                CosmosMsg::Bank(BankMsg::Send { amount, .. }) => {
-                    amount.iter().map(|c| c.amount.u128()).sum()
-                }
-                _ => unimplemented!(),
-            })
+            .map(|amount| amount.iter().map(|c| c.amount.u128()).sum())
             .collect();
         let total_distributions: u128 = amounts.iter().sum();
         // println!("{:#?}", res);
         // println!("{:#?}", total_distributions);
         // println!("{:?}", deps.api.human_address(&state.owner).unwrap());
-        assert!(total_distributions < 40_000)
+        // the pool and every contribution are paid out, except for the dust.
+        let res = query(&deps, mock_env(), QueryMsg::FinalDistributions {}).unwrap();
+        let value: FinalDistributionsResponse = from_binary(&res).unwrap();
+        let dust = value.snapshot.dust[0].amount.u128();
+        assert_eq!(10_000 + 30_000, total_distributions + dust);
+    }
+
+    #[test]
+    fn fail_distribute_funds_twice() {
+        let mut deps = mock_dependencies(&coins(10000, "uearth"));
+        mock_init_with_pool(&mut deps, default_init_msg(), &coins(10000, "uearth"));
+        mock_proposal(&mut deps, default_proposal_msg());
+        mock_proposal(&mut deps, default_proposal_msg());
+        mock_vote(&mut deps, "voter_0".to_string(), 0, coins(1000, "uearth"));
+        mock_vote(&mut deps, "voter_1".to_string(), 0, coins(9000, "uearth"));
+        mock_vote(&mut deps, "voter_2".to_string(), 1, coins(4000, "uearth"));
+        mock_vote(&mut deps, "voter_0".to_string(), 1, coins(16000, "uearth"));
+
+        // no snapshot before funds are distributed.
+        let res = query(&deps, mock_env(), QueryMsg::FinalDistributions {});
+        assert!(res.is_err());
+
+        let info = mock_info("owner", &[]);
+        let mut env = mock_env();
+        env.block.time += 86400 * 6;
+
+        let msg = HandleMsg::DistributeFunds {};
+        let _res = handle(&mut deps, env.clone(), info.clone(), msg.clone()).unwrap();
+        assert_eq!(2, claimable_amounts(&deps).len());
+
+        // second distribution is rejected.
+        let res = handle(&mut deps, env.clone(), info, msg);
+        match res {
+            Err(ContractError::AlreadyDistributed {}) => {}
+            _ => panic!("Must return error"),
+        }
+
+        // snapshot records what was paid.
+        let res = query(&deps, mock_env(), QueryMsg::FinalDistributions {}).unwrap();
+        let value: FinalDistributionsResponse = from_binary(&res).unwrap();
+        assert_eq!(env.block.time, value.snapshot.time);
+        assert_eq!(env.block.height, value.snapshot.height);
+        assert_eq!(2, value.snapshot.distributions.len());
     }
 
     #[test]
@@ -903,6 +1440,8 @@ mod tests {
                     .unwrap(),
                 description: "an okay proposal".to_string(),
                 tags: "money".to_string(),
+                milestones: vec![],
+                approved: true,
             },
             Proposal {
                 id: 1,
@@ -913,10 +1452,17 @@ mod tests {
                     .unwrap(),
                 description: "an better proposal".to_string(),
                 tags: "stuffed animals, parrots".to_string(),
+                milestones: vec![],
+                approved: true,
             },
         ];
-        let result: Vec<Distribution> =
-            calculate_distributions(votes, proposals, coins(100, "shell"));
+        let result: Vec<Distribution> = calculate_distributions(
+            &FundingFormula::Quadratic {},
+            votes,
+            proposals,
+            coins(100_000_000, "earth"),
+        )
+        .unwrap();
         // println!("{:#?}", result);
         assert_eq!(result.len(), 2);
         let distributions_for_prop_0: Vec<Distribution> = result
@@ -931,25 +1477,2488 @@ mod tests {
             .collect();
         assert_eq!(
             distributions_for_prop_0[0].subsidy_actual.amount.u128(),
-            14285714 as u128
+            14285714_u128
         );
         assert_eq!(
             distributions_for_prop_1[0].subsidy_actual.amount.u128(),
-            85714285 as u128
+            85714285_u128
         );
         assert_eq!(
             distributions_for_prop_0[0]
                 .distribution_actual
                 .amount
                 .u128(),
-            19285714 as u128
+            14285719_u128
         );
         assert_eq!(
             distributions_for_prop_1[0]
                 .distribution_actual
                 .amount
                 .u128(),
-            110714285 as u128
+            85714310_u128
         );
     }
+
+    fn mock_distribution_input(
+        votes: &[(&str, u32, u128)],
+        proposal_count: u32,
+    ) -> (Vec<Vote>, Vec<Proposal>) {
+        let deps = mock_dependencies(&[]);
+        let votes = votes
+            .iter()
+            .map(|(voter, proposal, amount)| Vote {
+                voter: deps
+                    .api
+                    .canonical_address(&HumanAddr(voter.to_string()))
+                    .unwrap(),
+                proposal: *proposal,
+                amount: coins(*amount, "earth"),
+            })
+            .collect();
+        let proposals = (0..proposal_count)
+            .map(|id| Proposal {
+                id,
+                name: format!("Proposal {}", id),
+                recipient: deps
+                    .api
+                    .canonical_address(&HumanAddr(format!("recipient_{}", id)))
+                    .unwrap(),
+                description: "a proposal".to_string(),
+                tags: "".to_string(),
+                milestones: vec![],
+                approved: true,
+            })
+            .collect();
+        (votes, proposals)
+    }
+
+    fn subsidies_actual(distributions: &[Distribution]) -> Vec<u128> {
+        distributions
+            .iter()
+            .map(|d| d.subsidy_actual.amount.u128())
+            .collect()
+    }
+
+    #[test]
+    fn test_calculate_distributions_linear() {
+        let (votes, proposals) = mock_distribution_input(
+            &[
+                ("voter_0", 0, 1),
+                ("voter_1", 0, 4),
+                ("voter_2", 1, 9),
+                ("voter_0", 1, 16),
+            ],
+            2,
+        );
+        let result = calculate_distributions(
+            &FundingFormula::Linear {},
+            votes,
+            proposals,
+            coins(100_000_000, "earth"),
+        )
+        .unwrap();
+        // matching is split 5:25 like the contributions.
+        assert_eq!(vec![16666666, 83333333], subsidies_actual(&result));
+        assert_eq!(16666671, result[0].distribution_actual.amount.u128());
+    }
+
+    #[test]
+    fn test_calculate_distributions_capped_quadratic() {
+        let (votes, proposals) = mock_distribution_input(
+            &[
+                ("voter_0", 0, 1),
+                ("voter_1", 0, 4),
+                ("voter_2", 1, 9),
+                ("voter_0", 1, 16),
+            ],
+            2,
+        );
+        let result = calculate_distributions(
+            &FundingFormula::CappedQuadratic {
+                cap: Decimal::percent(60),
+            },
+            votes,
+            proposals,
+            coins(100_000_000, "earth"),
+        )
+        .unwrap();
+        // proposal 1 is capped at 60% of the pool, and the rest goes to proposal 0.
+        assert_eq!(vec![40_000_000, 60_000_000], subsidies_actual(&result));
+    }
+
+    #[test]
+    fn init_with_funding_formula() {
+        let mut deps = mock_dependencies(&[]);
+        let mut msg = default_init_msg();
+        msg.funding_formula = Some(FundingFormula::Linear {});
+        mock_init(&mut deps, msg);
+
+        let res = query(&deps, mock_env(), QueryMsg::GetState {}).unwrap();
+        let value: StateResponse = from_binary(&res).unwrap();
+        assert_eq!(FundingFormula::Linear {}, value.funding_formula);
+
+        // quadratic funding is the default.
+        let mut deps = mock_dependencies(&[]);
+        mock_init(&mut deps, default_init_msg());
+        let res = query(&deps, mock_env(), QueryMsg::GetState {}).unwrap();
+        let value: StateResponse = from_binary(&res).unwrap();
+        assert_eq!(FundingFormula::Quadratic {}, value.funding_formula);
+    }
+
+    #[test]
+    fn test_calculate_distributions_pairwise_matches_quadratic_for_large_bound() {
+        let input = [
+            ("voter_0", 0, 1),
+            ("voter_1", 0, 4),
+            ("voter_2", 1, 9),
+            ("voter_0", 1, 16),
+        ];
+        let (votes, proposals) = mock_distribution_input(&input, 2);
+        let quadratic = calculate_distributions(
+            &FundingFormula::Quadratic {},
+            votes.clone(),
+            proposals.clone(),
+            coins(100_000_000, "earth"),
+        )
+        .unwrap();
+        let pairwise = calculate_distributions(
+            &FundingFormula::PairwiseBounded {
+                bounds: coins(1_000_000_000_000_000_000, "earth"),
+            },
+            votes,
+            proposals,
+            coins(100_000_000, "earth"),
+        )
+        .unwrap();
+        // with no effective bound the results only differ by rounding.
+        for (q, p) in quadratic.iter().zip(pairwise.iter()) {
+            let q_ideal = q.subsidy_ideal.amount.u128();
+            let p_ideal = p.subsidy_ideal.amount.u128();
+            assert!(q_ideal - p_ideal <= 1);
+            let q_actual = q.subsidy_actual.amount.u128();
+            let p_actual = p.subsidy_actual.amount.u128();
+            assert!(q_actual.max(p_actual) - q_actual.min(p_actual) <= 1);
+        }
+    }
+
+    #[test]
+    fn test_calculate_distributions_pairwise_dampens_coordinated_voters() {
+        // voter_0 and voter_1 co-fund proposals 0, 1 and 2, while voter_2 and
+        // voter_3 only fund proposal 3 together.
+        let input = [
+            ("voter_0", 0, 100),
+            ("voter_1", 0, 100),
+            ("voter_0", 1, 100),
+            ("voter_1", 1, 100),
+            ("voter_0", 2, 100),
+            ("voter_1", 2, 100),
+            ("voter_2", 3, 100),
+            ("voter_3", 3, 100),
+        ];
+        let (votes, proposals) = mock_distribution_input(&input, 4);
+
+        // plain quadratic funding matches every proposal equally.
+        let quadratic = calculate_distributions(
+            &FundingFormula::Quadratic {},
+            votes.clone(),
+            proposals.clone(),
+            coins(1_000_000_000, "earth"),
+        )
+        .unwrap();
+        let quadratic = subsidies_actual(&quadratic);
+        assert_eq!(quadratic[0], quadratic[3]);
+
+        // with M equal to a single contribution the coordinated pair's matching
+        // is multiplied by 1/4 and the independent pair's by 1/2.
+        let pairwise = calculate_distributions(
+            &FundingFormula::PairwiseBounded {
+                bounds: coins(100, "earth"),
+            },
+            votes,
+            proposals,
+            coins(1_000_000_000, "earth"),
+        )
+        .unwrap();
+        assert_eq!(
+            vec![50, 50, 50, 100],
+            pairwise
+                .iter()
+                .map(|d| d.subsidy_ideal.amount.u128())
+                .collect::<Vec<_>>()
+        );
+        let pairwise = subsidies_actual(&pairwise);
+        assert_eq!(pairwise[0] * 2, pairwise[3]);
+        assert!(pairwise[3] > quadratic[3]);
+        assert!(pairwise[0] < quadratic[0]);
+    }
+
+    #[test]
+    fn pairwise_bound_per_denom() {
+        let (earth_votes, proposals) = mock_distribution_input(
+            &[
+                ("voter_0", 0, 100),
+                ("voter_1", 0, 100),
+                ("voter_0", 1, 100),
+                ("voter_1", 1, 100),
+                ("voter_2", 2, 100),
+                ("voter_3", 2, 100),
+            ],
+            3,
+        );
+        let uearth_votes: Vec<Vote> = earth_votes
+            .iter()
+            .cloned()
+            .map(|mut v| {
+                v.amount = coins(v.amount[0].amount.u128(), "uearth");
+                v
+            })
+            .collect();
+        let votes: Vec<Vote> = earth_votes.into_iter().chain(uearth_votes).collect();
+        let formula = FundingFormula::PairwiseBounded {
+            bounds: vec![coin(1, "earth"), coin(1_000_000_000, "uearth")],
+        };
+        let budget = vec![coin(3000, "earth"), coin(3000, "uearth")];
+        let result = calculate_distributions(&formula, votes, proposals, budget).unwrap();
+        let subsidy = |denom: &str, proposal: u32| {
+            result
+                .iter()
+                .find(|d| d.proposal == proposal && d.subsidy_actual.denom == denom)
+                .map(|d| d.subsidy_actual.amount.u128())
+                .unwrap()
+        };
+        // a tight bound penalizes the pair co-funding twice, a loose one barely does.
+        assert!(subsidy("earth", 2) > subsidy("earth", 0) * 19 / 10);
+        assert!(subsidy("uearth", 2) - subsidy("uearth", 0) <= 1);
+    }
+
+    #[test]
+    fn fails_initialization_invalid_funding_formula() {
+        let mut deps = mock_dependencies(&[]);
+        let info = mock_info("owner", &[]);
+
+        for bounds in [
+            vec![coin(0, "earth"), coin(1000, "uearth")],
+            // each accepted denom needs exactly one bound.
+            coins(1000, "earth"),
+            vec![coin(1000, "earth"), coin(1000, "earth")],
+            vec![
+                coin(1000, "earth"),
+                coin(1000, "uearth"),
+                coin(1000, "shell"),
+            ],
+        ]
+        .iter()
+        {
+            let mut msg = default_init_msg();
+            msg.funding_formula = Some(FundingFormula::PairwiseBounded {
+                bounds: bounds.clone(),
+            });
+            let res = init(&mut deps, mock_env(), info.clone(), msg);
+            match res {
+                Err(ContractError::InvalidFundingFormula { reason: _ }) => {}
+                _ => panic!("Must return error"),
+            }
+        }
+
+        let mut msg = default_init_msg();
+        msg.funding_formula = Some(FundingFormula::CappedQuadratic {
+            cap: Decimal::percent(150),
+        });
+        let res = init(&mut deps, mock_env(), info, msg);
+        match res {
+            Err(ContractError::InvalidFundingFormula { reason: _ }) => {}
+            _ => panic!("Must return error"),
+        }
+    }
+
+    #[test]
+    fn distribute_funds_pairwise_bounded() {
+        let mut deps = mock_dependencies(&coins(10000, "uearth"));
+        let mut msg = default_init_msg();
+        msg.funding_formula = Some(FundingFormula::PairwiseBounded {
+            bounds: vec![coin(1000, "earth"), coin(1000, "uearth")],
+        });
+        mock_init_with_pool(&mut deps, msg, &coins(10000, "uearth"));
+        mock_proposal(&mut deps, default_proposal_msg());
+        mock_proposal(&mut deps, default_proposal_msg());
+        mock_vote(&mut deps, "voter_0".to_string(), 0, coins(1000, "uearth"));
+        mock_vote(&mut deps, "voter_1".to_string(), 0, coins(9000, "uearth"));
+        mock_vote(&mut deps, "voter_0".to_string(), 1, coins(4000, "uearth"));
+        mock_vote(&mut deps, "voter_1".to_string(), 1, coins(16000, "uearth"));
+        mock_vote(&mut deps, "voter_2".to_string(), 1, coins(1000, "uearth"));
+
+        let mut env = mock_env();
+        env.block.time += 86400 * 6;
+        let _res = handle(
+            &mut deps,
+            env,
+            mock_info("owner", &[]),
+            HandleMsg::DistributeFunds {},
+        )
+        .unwrap();
+        assert_eq!(2, claimable_amounts(&deps).len());
+
+        let res = query(&deps, mock_env(), QueryMsg::FinalDistributions {}).unwrap();
+        let value: FinalDistributionsResponse = from_binary(&res).unwrap();
+        let subsidies = subsidies_actual(&value.snapshot.distributions);
+        assert!(subsidies.iter().all(|s| *s > 0));
+    }
+
+    #[test]
+    fn fixed_point_precision() {
+        let scale = Fixed::from_u128(1_000_000_000_000_000_000);
+        assert_eq!(
+            1_414_213_562_373_095_048,
+            Fixed::from_u128(2)
+                .sqrt()
+                .checked_mul(scale)
+                .unwrap()
+                .floor()
+        );
+        assert_eq!(
+            333_333_333_333_333_333,
+            Fixed::from_ratio(1, 3).checked_mul(scale).unwrap().floor()
+        );
+        assert_eq!(3, Fixed::from_u128(9).sqrt().floor());
+        // products are only rejected when the result is out of range.
+        let large = Fixed::from_u128(u128::MAX);
+        assert_eq!(None, large.checked_mul(large));
+        assert_eq!(Some(u128::MAX), large.sqrt_product(large).checked_floor());
+        assert_eq!(
+            Some(u128::MAX / 2),
+            large.mul_div_floor(u128::MAX, large.checked_mul(Fixed::from_u128(2)).unwrap())
+        );
+    }
+
+    #[test]
+    fn test_calculate_distributions_tiny_subsidies() {
+        // subsidies that are tiny relative to the budget used to round the
+        // constraint factor down to zero.
+        let (votes, proposals) = mock_distribution_input(
+            &[("voter_0", 0, 1), ("voter_1", 0, 4), ("voter_2", 1, 9)],
+            2,
+        );
+        let votes: Vec<Vote> = votes
+            .into_iter()
+            .map(|mut v| {
+                v.amount = coins(v.amount[0].amount.u128(), "uearth");
+                v
+            })
+            .collect();
+        let budget = coins(1_000_000_000_000, "uearth");
+        let result = calculate_distributions(
+            &FundingFormula::Quadratic {},
+            votes,
+            proposals,
+            budget.clone(),
+        )
+        .unwrap();
+        assert_eq!(4, result[0].subsidy_ideal.amount.u128());
+        assert_eq!(1_000_000_000_000, result[0].subsidy_actual.amount.u128());
+        assert_eq!(0, result[1].subsidy_actual.amount.u128());
+        assert_eq!(
+            0,
+            calculate_dust(&result, &budget).unwrap()[0].amount.u128()
+        );
+    }
+
+    #[test]
+    fn test_calculate_distributions_conserves_budget() {
+        let (votes, proposals) = mock_distribution_input(
+            &[
+                ("voter_0", 0, 3),
+                ("voter_1", 0, 7),
+                ("voter_2", 1, 11),
+                ("voter_0", 1, 2),
+                ("voter_1", 2, 5),
+                ("voter_2", 2, 13),
+            ],
+            3,
+        );
+        let contributions: u128 = 41;
+        let budget = coins(1_000_001, "earth");
+        for formula in [
+            FundingFormula::Quadratic {},
+            FundingFormula::Linear {},
+            FundingFormula::CappedQuadratic {
+                cap: Decimal::percent(40),
+            },
+            FundingFormula::PairwiseBounded {
+                bounds: coins(1_000_000, "earth"),
+            },
+        ]
+        .iter()
+        {
+            let result =
+                calculate_distributions(formula, votes.clone(), proposals.clone(), budget.clone())
+                    .unwrap();
+            let paid: u128 = result
+                .iter()
+                .map(|d| d.distribution_actual.amount.u128())
+                .sum();
+            let dust = calculate_dust(&result, &budget).unwrap()[0].amount.u128();
+            assert!(paid <= 1_000_001 + contributions);
+            assert_eq!(1_000_001 + contributions, paid + dust);
+        }
+    }
+
+    #[test]
+    fn test_calculate_distributions_large_amounts() {
+        // 1000 tokens of an 18 decimal denom per vote.
+        let amount = 1_000_000_000_000_000_000_000u128;
+        let (votes, proposals) = mock_distribution_input(
+            &[
+                ("voter_0", 0, amount),
+                ("voter_1", 0, amount),
+                ("voter_2", 1, amount * 3),
+                ("voter_0", 1, amount / 7),
+            ],
+            2,
+        );
+        let contributions = amount * 2 + amount * 3 + amount / 7;
+        let budget = coins(amount * 10, "earth");
+        let formulas = [
+            FundingFormula::Quadratic {},
+            FundingFormula::Linear {},
+            FundingFormula::CappedQuadratic {
+                cap: Decimal::percent(60),
+            },
+            FundingFormula::PairwiseBounded {
+                bounds: coins(amount, "earth"),
+            },
+        ];
+        for formula in formulas.iter() {
+            let result =
+                calculate_distributions(formula, votes.clone(), proposals.clone(), budget.clone())
+                    .unwrap();
+            let paid: u128 = result
+                .iter()
+                .map(|d| d.distribution_actual.amount.u128())
+                .sum();
+            let dust = calculate_dust(&result, &budget).unwrap()[0].amount.u128();
+            assert_eq!(amount * 10 + contributions, paid + dust);
+        }
+    }
+
+    #[test]
+    fn test_calculate_distributions_overflow() {
+        // the ideal subsidy is larger than any amount of coins.
+        let amount = u128::MAX / 4;
+        let (votes, proposals) = mock_distribution_input(
+            &[
+                ("voter_0", 0, amount),
+                ("voter_1", 0, amount),
+                ("voter_2", 0, amount),
+            ],
+            1,
+        );
+        let res = calculate_distributions(
+            &FundingFormula::Quadratic {},
+            votes,
+            proposals,
+            coins(1000, "earth"),
+        );
+        match res {
+            Err(ContractError::MatchingOverflow { denom }) => assert_eq!("earth", denom),
+            _ => panic!("Must return error"),
+        }
+    }
+
+    fn mock_distribute_funds(
+        deps: &mut Extern<MockStorage, MockApi, MockQuerier>,
+    ) -> Result<HandleResponse, ContractError> {
+        let mut env = mock_env();
+        env.block.time += 86400 * 6;
+        handle(
+            deps,
+            env,
+            mock_info("owner", &[]),
+            HandleMsg::DistributeFunds {},
+        )
+    }
+
+    fn claimable_amounts(deps: &Extern<MockStorage, MockApi, MockQuerier>) -> Vec<Vec<Coin>> {
+        let msg = QueryMsg::Claims {
+            start_after: None,
+            limit: None,
+        };
+        let res = query(deps, mock_env(), msg).unwrap();
+        let value: ClaimsResponse = from_binary(&res).unwrap();
+        value.claims.into_iter().map(|c| c.amount).collect()
+    }
+
+    #[test]
+    fn distribute_funds_empty_round() {
+        // no proposals and no votes.
+        let mut deps = mock_dependencies(&coins(10000, "uearth"));
+        mock_init_with_pool(&mut deps, default_init_msg(), &coins(10000, "uearth"));
+
+        mock_distribute_funds(&mut deps).unwrap();
+        assert!(claimable_amounts(&deps).is_empty());
+
+        let res = query(&deps, mock_env(), QueryMsg::FinalDistributions {}).unwrap();
+        let value: FinalDistributionsResponse = from_binary(&res).unwrap();
+        assert_eq!(0, value.snapshot.distributions.len());
+        assert_eq!(coins(10000, "uearth"), value.snapshot.dust);
+    }
+
+    #[test]
+    fn distribute_funds_no_votes() {
+        let mut deps = mock_dependencies(&coins(10000, "uearth"));
+        mock_init_with_pool(&mut deps, default_init_msg(), &coins(10000, "uearth"));
+        mock_proposal(&mut deps, default_proposal_msg());
+        mock_proposal(&mut deps, default_proposal_msg());
+
+        // nothing can be claimed by proposals without votes.
+        mock_distribute_funds(&mut deps).unwrap();
+        assert!(claimable_amounts(&deps).is_empty());
+
+        let res = query(&deps, mock_env(), QueryMsg::FinalDistributions {}).unwrap();
+        let value: FinalDistributionsResponse = from_binary(&res).unwrap();
+        assert_eq!(2, value.snapshot.distributions.len());
+        assert_eq!(coins(10000, "uearth"), value.snapshot.dust);
+    }
+
+    #[test]
+    fn distribute_funds_single_voter() {
+        let mut deps = mock_dependencies(&coins(10000, "uearth"));
+        mock_init_with_pool(&mut deps, default_init_msg(), &coins(10000, "uearth"));
+        mock_proposal(&mut deps, default_proposal_msg());
+        mock_proposal(&mut deps, default_proposal_msg());
+        mock_vote(&mut deps, "voter_0".to_string(), 0, coins(1000, "uearth"));
+        mock_vote(&mut deps, "voter_0".to_string(), 1, coins(3000, "uearth"));
+
+        // a single voter gets no matching, contributions are returned.
+        mock_distribute_funds(&mut deps).unwrap();
+        assert_eq!(
+            vec![coins(1000, "uearth"), coins(3000, "uearth")],
+            claimable_amounts(&deps)
+        );
+    }
+
+    #[test]
+    fn distribute_funds_without_pool() {
+        let mut deps = mock_dependencies(&[]);
+        mock_init_with_pool(&mut deps, default_init_msg(), &[]);
+        mock_proposal(&mut deps, default_proposal_msg());
+        mock_vote(&mut deps, "voter_0".to_string(), 0, coins(100, "earth"));
+        mock_vote(&mut deps, "voter_1".to_string(), 0, coins(300, "earth"));
+
+        // nothing is matched, but contributions aren't locked in the contract.
+        let res = mock_distribute_funds(&mut deps).unwrap();
+        assert!(res.messages.is_empty());
+        assert_eq!(vec![coins(400, "earth")], claimable_amounts(&deps));
+        let snapshot = query_snapshot(&deps);
+        assert_eq!(coin(0, "earth"), snapshot.distributions[0].subsidy_actual);
+        assert!(snapshot.dust.is_empty());
+    }
+
+    #[test]
+    fn fail_distribute_funds_empty_balance() {
+        let mut deps = mock_dependencies(&[]);
+        mock_init_with_pool(&mut deps, default_init_msg(), &[]);
+        mock_proposal(&mut deps, default_proposal_msg());
+
+        let res = mock_distribute_funds(&mut deps);
+        match res {
+            Err(ContractError::EmptyBudget {}) => {}
+            _ => panic!("Must return error"),
+        }
+
+        // the round is not finalized.
+        let res = query(&deps, mock_env(), QueryMsg::FinalDistributions {});
+        assert!(res.is_err());
+
+        let mut env = mock_env();
+        env.block.time += 86400 * 3;
+        let msg = QueryMsg::Distributions {
+            hypothetical_vote: None,
+        };
+        assert!(query(&deps, env, msg).is_err());
+    }
+
+    #[test]
+    fn fail_init_no_accepted_denoms() {
+        let mut deps = mock_dependencies(&[]);
+        let mut msg = default_init_msg();
+        msg.accepted_denoms = vec![];
+        let info = mock_info("owner", &[]);
+        let res = init(&mut deps, mock_env(), info, msg);
+        match res {
+            Err(ContractError::NoAcceptedDenoms {}) => {}
+            _ => panic!("Must return error"),
+        }
+    }
+
+    #[test]
+    fn distribute_funds_per_denom() {
+        let mut deps = mock_dependencies(&[coin(1000, "earth"), coin(1000, "uearth")]);
+        mock_init_with_pool(
+            &mut deps,
+            default_init_msg(),
+            &[coin(1000, "earth"), coin(1000, "uearth")],
+        );
+        mock_proposal(&mut deps, default_proposal_msg());
+        mock_proposal(&mut deps, default_proposal_msg());
+        mock_vote(&mut deps, "voter_0".to_string(), 0, coins(100, "earth"));
+        mock_vote(&mut deps, "voter_1".to_string(), 0, coins(100, "earth"));
+        mock_vote(&mut deps, "voter_1".to_string(), 1, coins(100, "uearth"));
+        mock_vote(&mut deps, "voter_2".to_string(), 1, coins(100, "uearth"));
+
+        mock_distribute_funds(&mut deps).unwrap();
+
+        // each pool only matches the votes made in its denom.
+        assert_eq!(
+            vec![coins(1200, "earth"), coins(1200, "uearth")],
+            claimable_amounts(&deps)
+        );
+
+        let res = query(&deps, mock_env(), QueryMsg::FinalDistributions {}).unwrap();
+        let value: FinalDistributionsResponse = from_binary(&res).unwrap();
+        assert_eq!(4, value.snapshot.distributions.len());
+        assert_eq!(
+            vec![coin(0, "earth"), coin(0, "uearth")],
+            value.snapshot.dust
+        );
+    }
+
+    fn query_round_phase(
+        deps: &Extern<MockStorage, MockApi, MockQuerier>,
+        time: u64,
+    ) -> PhaseResponse {
+        let mut env = mock_env();
+        env.block.time = time;
+        let res = query(deps, env, QueryMsg::Phase {}).unwrap();
+        from_binary(&res).unwrap()
+    }
+
+    #[test]
+    fn query_phase_open_ended() {
+        let mut deps = mock_dependencies(&[]);
+        let mut msg = default_init_msg();
+        msg.voting_period_end = Some(Expiration::AtTime(u64::MAX));
+        mock_init(&mut deps, msg);
+        let t = mock_env().block.time;
+        let value = query_round_phase(&deps, t + 86400 * 3);
+        assert_eq!(RoundPhase::Voting, value.phase);
+        assert_eq!(
+            vec![PhaseDeadline {
+                phase: RoundPhase::Tally,
+                at: Expiration::AtTime(u64::MAX),
+            }],
+            value.deadlines
+        );
+    }
+
+    #[test]
+    fn query_phase() {
+        let mut deps = mock_dependencies(&[]);
+        mock_init(&mut deps, empty_period_init_msg());
+        let value = query_round_phase(&deps, mock_env().block.time);
+        assert_eq!(RoundPhase::Setup, value.phase);
+        assert!(value.deadlines.is_empty());
+
+        let mut deps = mock_dependencies(&coins(1000, "earth"));
+        mock_init(&mut deps, default_init_msg());
+        let t = mock_env().block.time;
+        let value = query_round_phase(&deps, t);
+        assert_eq!(RoundPhase::Proposals, value.phase);
+        assert_eq!(
+            vec![
+                PhaseDeadline {
+                    phase: RoundPhase::Review,
+                    at: Expiration::AtTime(t + 86400 + 1)
+                },
+                PhaseDeadline {
+                    phase: RoundPhase::Voting,
+                    at: Expiration::AtTime(t + 86400 * 2)
+                },
+                PhaseDeadline {
+                    phase: RoundPhase::Tally,
+                    at: Expiration::AtTime(t + 86400 * 5 + 1)
+                },
+            ],
+            value.deadlines
+        );
+        assert_eq!(
+            RoundPhase::Review,
+            query_round_phase(&deps, t + 86400 + 1).phase
+        );
+        let value = query_round_phase(&deps, t + 86400 * 3);
+        assert_eq!(RoundPhase::Voting, value.phase);
+        assert_eq!(1, value.deadlines.len());
+        assert_eq!(
+            RoundPhase::Tally,
+            query_round_phase(&deps, t + 86400 * 6).phase
+        );
+
+        mock_proposal(&mut deps, default_proposal_msg());
+        mock_distribute_funds(&mut deps).unwrap();
+        let value = query_round_phase(&deps, t + 86400 * 6);
+        assert_eq!(RoundPhase::Distributed, value.phase);
+        assert!(value.deadlines.is_empty());
+    }
+
+    #[test]
+    fn fail_create_proposal_after_distribution() {
+        let mut deps = mock_dependencies(&coins(1000, "earth"));
+        mock_init(&mut deps, default_init_msg());
+        mock_distribute_funds(&mut deps).unwrap();
+
+        // the schedule alone would allow proposals now, but the round is finished.
+        let info = mock_info("proposer_0", &[]);
+        let res = handle(&mut deps, mock_env(), info, default_proposal_msg());
+        match res {
+            Err(ContractError::AlreadyDistributed {}) => {}
+            _ => panic!("Must return error"),
+        }
+    }
+
+    #[test]
+    fn height_based_schedule() {
+        let mut deps = mock_dependencies(&coins(1000, "earth"));
+        let h = mock_env().block.height;
+        let mut msg = empty_period_init_msg();
+        msg.proposal_period_start = Some(Expiration::AtHeight(h));
+        msg.proposal_period_end = Some(Expiration::AtHeight(h + 10));
+        msg.voting_period_start = Some(Expiration::AtHeight(h + 20));
+        mock_init(&mut deps, msg);
+        mock_proposal(&mut deps, default_proposal_msg());
+
+        // the block time doesn't matter, only the height.
+        let mut env = mock_env();
+        env.block.time += 86400 * 100;
+        env.block.height = h + 15;
+        let res = query(&deps, env.clone(), QueryMsg::Phase {}).unwrap();
+        let value: PhaseResponse = from_binary(&res).unwrap();
+        assert_eq!(RoundPhase::Review, value.phase);
+        assert_eq!(h + 15, value.height);
+        assert_eq!(
+            vec![PhaseDeadline {
+                phase: RoundPhase::Voting,
+                at: Expiration::AtHeight(h + 20)
+            }],
+            value.deadlines
+        );
+
+        let info = mock_info("voter_0", &coins(100, "earth"));
+        let msg = HandleMsg::CreateVote {
+            proposal_id: 0,
+            proof: None,
+        };
+        let res = handle(&mut deps, env.clone(), info.clone(), msg.clone());
+        match res {
+            Err(ContractError::InvalidPeriod { period_type: _ }) => {}
+            _ => panic!("Must return error"),
+        }
+        env.block.height = h + 20;
+        let _res = handle(&mut deps, env.clone(), info, msg).unwrap();
+
+        // ending voting without a bound uses the current height.
+        let owner = mock_info("owner", &[]);
+        let end_voting = HandleMsg::EndVotingPeriod { at: None };
+        let _res = handle(&mut deps, env.clone(), owner, end_voting).unwrap();
+        let res = query(&deps, env, QueryMsg::GetState {}).unwrap();
+        let value: StateResponse = from_binary(&res).unwrap();
+        assert_eq!(Some(Expiration::AtHeight(h + 20)), value.voting_period_end);
+    }
+
+    #[test]
+    fn fails_initialization_mixed_schedule() {
+        let mut msg = default_init_msg();
+        msg.voting_period_end = Some(Expiration::AtHeight(mock_env().block.height + 100));
+        assert_invalid_schedule(msg);
+    }
+
+    fn mock_claim_refund(
+        deps: &mut Extern<MockStorage, MockApi, MockQuerier>,
+        sender: &str,
+    ) -> Result<HandleResponse, ContractError> {
+        let mut env = mock_env();
+        env.block.time += 86400 * 3;
+        handle(deps, env, mock_info(sender, &[]), HandleMsg::ClaimRefund {})
+    }
+
+    #[test]
+    fn cancel_round_refunds() {
+        let mut deps = mock_dependencies(&[]);
+        mock_init(&mut deps, default_init_msg());
+        mock_proposal(&mut deps, default_proposal_msg());
+        mock_proposal(&mut deps, default_proposal_msg());
+        mock_vote(&mut deps, "voter_0".to_string(), 0, coins(100, "earth"));
+        mock_vote(&mut deps, "voter_0".to_string(), 1, coins(50, "uearth"));
+        mock_vote(&mut deps, "voter_0".to_string(), 1, coins(25, "earth"));
+        mock_vote(&mut deps, "voter_1".to_string(), 0, coins(200, "earth"));
+
+        // refunds are only available once the round is cancelled.
+        match mock_claim_refund(&mut deps, "voter_0") {
+            Err(ContractError::InvalidPeriod { period_type: _ }) => {}
+            _ => panic!("Must return error"),
+        }
+
+        // only the owner can cancel.
+        let mut env = mock_env();
+        env.block.time += 86400 * 3;
+        let msg = HandleMsg::CancelRound {};
+        let res = handle(
+            &mut deps,
+            env.clone(),
+            mock_info("voter_0", &[]),
+            msg.clone(),
+        );
+        match res {
+            Err(ContractError::Unauthorized { list_type: _ }) => {}
+            _ => panic!("Must return error"),
+        }
+        let _res = handle(&mut deps, env.clone(), mock_info("owner", &[]), msg).unwrap();
+
+        let res = query(&deps, env.clone(), QueryMsg::Phase {}).unwrap();
+        let value: PhaseResponse = from_binary(&res).unwrap();
+        assert_eq!(RoundPhase::Cancelled, value.phase);
+        assert!(value.deadlines.is_empty());
+
+        // a cancelled round takes no votes and can't be distributed.
+        let info = mock_info("voter_2", &coins(100, "earth"));
+        let res = handle(
+            &mut deps,
+            env,
+            info,
+            HandleMsg::CreateVote {
+                proposal_id: 0,
+                proof: None,
+            },
+        );
+        match res {
+            Err(ContractError::RoundCancelled {}) => {}
+            _ => panic!("Must return error"),
+        }
+        match mock_distribute_funds(&mut deps) {
+            Err(ContractError::RoundCancelled {}) => {}
+            _ => panic!("Must return error"),
+        }
+
+        // voters get back exactly what they contributed, and the owner their deposit.
+        let refunded = |res: HandleResponse| match &res.messages[0] {
+            CosmosMsg::Bank(BankMsg::Send {
+                to_address, amount, ..
+            }) => (to_address.clone(), amount.clone()),
+            _ => unimplemented!(),
+        };
+        let res = mock_claim_refund(&mut deps, "voter_0").unwrap();
+        assert_eq!(
+            (
+                HumanAddr::from("voter_0"),
+                vec![coin(125, "earth"), coin(50, "uearth")]
+            ),
+            refunded(res)
+        );
+        let res = mock_claim_refund(&mut deps, "voter_1").unwrap();
+        assert_eq!(coins(200, "earth"), refunded(res).1);
+        let res = mock_claim_refund(&mut deps, "owner").unwrap();
+        assert_eq!(coins(1000, "earth"), refunded(res).1);
+
+        match mock_claim_refund(&mut deps, "voter_0") {
+            Err(ContractError::AlreadyRefunded {}) => {}
+            _ => panic!("Must return error"),
+        }
+        match mock_claim_refund(&mut deps, "voter_2") {
+            Err(ContractError::NothingToRefund {}) => {}
+            _ => panic!("Must return error"),
+        }
+    }
+
+    #[test]
+    fn fail_cancel_round_after_distribution() {
+        let mut deps = mock_dependencies(&coins(1000, "earth"));
+        mock_init(&mut deps, default_init_msg());
+        mock_distribute_funds(&mut deps).unwrap();
+
+        let msg = HandleMsg::CancelRound {};
+        let res = handle(&mut deps, mock_env(), mock_info("owner", &[]), msg);
+        match res {
+            Err(ContractError::AlreadyDistributed {}) => {}
+            _ => panic!("Must return error"),
+        }
+    }
+
+    fn mock_claim(
+        deps: &mut Extern<MockStorage, MockApi, MockQuerier>,
+        sender: &str,
+        proposal_id: u32,
+        days: u64,
+    ) -> Result<HandleResponse, ContractError> {
+        let mut env = mock_env();
+        env.block.time += 86400 * days;
+        let msg = HandleMsg::Claim { proposal_id };
+        handle(deps, env, mock_info(sender, &[]), msg)
+    }
+
+    #[test]
+    fn claim_distribution() {
+        let mut deps = mock_dependencies(&coins(1000, "earth"));
+        mock_init(&mut deps, default_init_msg());
+        mock_proposal(&mut deps, default_proposal_msg());
+        mock_proposal(&mut deps, default_proposal_msg());
+        mock_vote(&mut deps, "voter_0".to_string(), 0, coins(100, "earth"));
+        mock_vote(&mut deps, "voter_1".to_string(), 0, coins(100, "earth"));
+
+        // nothing can be claimed before funds are distributed.
+        match mock_claim(&mut deps, "proposal_recipient", 0, 6) {
+            Err(ContractError::InvalidPeriod { period_type: _ }) => {}
+            _ => panic!("Must return error"),
+        }
+        mock_distribute_funds(&mut deps).unwrap();
+
+        // only the recipient can claim.
+        match mock_claim(&mut deps, "voter_0", 0, 6) {
+            Err(ContractError::Unauthorized { list_type: _ }) => {}
+            _ => panic!("Must return error"),
+        }
+        let res = mock_claim(&mut deps, "proposal_recipient", 0, 6).unwrap();
+        assert_eq!(
+            vec![CosmosMsg::Bank(BankMsg::Send {
+                from_address: mock_env().contract.address,
+                to_address: HumanAddr::from("proposal_recipient"),
+                amount: coins(1200, "earth"),
+            })],
+            res.messages
+        );
+        match mock_claim(&mut deps, "proposal_recipient", 0, 6) {
+            Err(ContractError::AlreadyClaimed {}) => {}
+            _ => panic!("Must return error"),
+        }
+
+        // proposal 1 had no votes, so it has nothing to claim.
+        match mock_claim(&mut deps, "proposal_recipient", 1, 6) {
+            Err(ContractError::NothingToClaim {}) => {}
+            _ => panic!("Must return error"),
+        }
+
+        let msg = QueryMsg::Claims {
+            start_after: None,
+            limit: None,
+        };
+        let res = query(&deps, mock_env(), msg).unwrap();
+        let value: ClaimsResponse = from_binary(&res).unwrap();
+        assert_eq!(1, value.claims.len());
+        assert_eq!(ClaimStatus::Claimed, value.claims[0].status);
+        assert_eq!(None, value.deadline);
+    }
+
+    #[test]
+    fn return_unclaimed_after_deadline() {
+        let mut deps = mock_dependencies(&coins(1000, "earth"));
+        let mut msg = default_init_msg();
+        let deadline = Expiration::AtTime(mock_env().block.time + 86400 * 10);
+        msg.claim_deadline = Some(deadline);
+        mock_init(&mut deps, msg);
+        mock_proposal(&mut deps, default_proposal_msg());
+        mock_proposal(&mut deps, default_proposal_msg());
+        mock_vote(&mut deps, "voter_0".to_string(), 0, coins(100, "earth"));
+        mock_vote(&mut deps, "voter_1".to_string(), 0, coins(100, "earth"));
+        mock_vote(&mut deps, "voter_0".to_string(), 1, coins(100, "earth"));
+        mock_vote(&mut deps, "voter_2".to_string(), 1, coins(100, "earth"));
+        mock_distribute_funds(&mut deps).unwrap();
+
+        // unclaimed funds can't be returned while claims are open.
+        let mut env = mock_env();
+        env.block.time += 86400 * 6;
+        let msg = HandleMsg::ReturnUnclaimed {};
+        let res = handle(&mut deps, env.clone(), mock_info("owner", &[]), msg.clone());
+        match res {
+            Err(ContractError::ClaimPeriodOpen {}) => {}
+            _ => panic!("Must return error"),
+        }
+        let _res = mock_claim(&mut deps, "proposal_recipient", 0, 6).unwrap();
+
+        // after the deadline the remaining claim goes back to the owner.
+        match mock_claim(&mut deps, "proposal_recipient", 1, 11) {
+            Err(ContractError::ClaimExpired {}) => {}
+            _ => panic!("Must return error"),
+        }
+        env.block.time += 86400 * 5;
+        let res = handle(
+            &mut deps,
+            env.clone(),
+            mock_info("voter_0", &[]),
+            msg.clone(),
+        );
+        match res {
+            Err(ContractError::Unauthorized { list_type: _ }) => {}
+            _ => panic!("Must return error"),
+        }
+        let res = handle(&mut deps, env.clone(), mock_info("owner", &[]), msg.clone()).unwrap();
+        assert_eq!(
+            vec![CosmosMsg::Bank(BankMsg::Send {
+                from_address: mock_env().contract.address,
+                to_address: HumanAddr::from("owner"),
+                amount: coins(700, "earth"),
+            })],
+            res.messages
+        );
+        let res = handle(&mut deps, env, mock_info("owner", &[]), msg);
+        match res {
+            Err(ContractError::NothingToClaim {}) => {}
+            _ => panic!("Must return error"),
+        }
+    }
+
+    #[test]
+    fn fails_initialization_claim_deadline_before_voting_end() {
+        let mut msg = default_init_msg();
+        msg.claim_deadline = msg.voting_period_end;
+        assert_invalid_schedule(msg);
+    }
+
+    fn vesting_init_msg() -> InitMsg {
+        let t = mock_env().block.time;
+        let mut msg = default_init_msg();
+        // vests over 100 days from the end of voting, with a 10 day cliff.
+        msg.vesting = Some(VestingSchedule {
+            start: Expiration::AtTime(t + 86400 * 5),
+            cliff: Expiration::AtTime(t + 86400 * 15),
+            end: Expiration::AtTime(t + 86400 * 105),
+        });
+        msg
+    }
+
+    fn mock_withdraw_vested(
+        deps: &mut Extern<MockStorage, MockApi, MockQuerier>,
+        days: u64,
+    ) -> Result<HandleResponse, ContractError> {
+        let mut env = mock_env();
+        env.block.time += 86400 * days;
+        let msg = HandleMsg::WithdrawVested { proposal_id: 0 };
+        handle(deps, env, mock_info("proposal_recipient", &[]), msg)
+    }
+
+    #[test]
+    fn withdraw_vested() {
+        let mut deps = mock_dependencies(&coins(800, "earth"));
+        mock_init_with_pool(&mut deps, vesting_init_msg(), &coins(800, "earth"));
+        mock_proposal(&mut deps, default_proposal_msg());
+        mock_vote(&mut deps, "voter_0".to_string(), 0, coins(100, "earth"));
+        mock_vote(&mut deps, "voter_1".to_string(), 0, coins(100, "earth"));
+        mock_distribute_funds(&mut deps).unwrap();
+
+        // the whole distribution can't be claimed at once.
+        match mock_claim(&mut deps, "proposal_recipient", 0, 6) {
+            Err(ContractError::VestingEnabled {}) => {}
+            _ => panic!("Must return error"),
+        }
+        // nothing is available before the cliff.
+        match mock_withdraw_vested(&mut deps, 14) {
+            Err(ContractError::NothingToClaim {}) => {}
+            _ => panic!("Must return error"),
+        }
+
+        let sent = |res: HandleResponse| match &res.messages[0] {
+            CosmosMsg::Bank(BankMsg::Send { amount, .. }) => amount.clone(),
+            _ => unimplemented!(),
+        };
+        // 10 of 100 days have vested at the cliff.
+        let res = mock_withdraw_vested(&mut deps, 15).unwrap();
+        assert_eq!(coins(100, "earth"), sent(res));
+        // halfway through only the newly vested part is paid.
+        let res = mock_withdraw_vested(&mut deps, 55).unwrap();
+        assert_eq!(coins(400, "earth"), sent(res));
+
+        let mut env = mock_env();
+        env.block.time += 86400 * 55;
+        let msg = QueryMsg::Vesting { proposal_id: 0 };
+        let res = query(&deps, env, msg).unwrap();
+        let value: VestingResponse = from_binary(&res).unwrap();
+        assert_eq!(coins(1000, "earth"), value.total);
+        assert_eq!(coins(500, "earth"), value.vested);
+        assert_eq!(coins(500, "earth"), value.withdrawn);
+
+        // everything is paid once vesting ends.
+        let res = mock_withdraw_vested(&mut deps, 200).unwrap();
+        assert_eq!(coins(500, "earth"), sent(res));
+        match mock_withdraw_vested(&mut deps, 201) {
+            Err(ContractError::AlreadyClaimed {}) => {}
+            _ => panic!("Must return error"),
+        }
+    }
+
+    #[test]
+    fn fail_withdraw_vested_without_vesting() {
+        let mut deps = mock_dependencies(&coins(800, "earth"));
+        mock_init(&mut deps, default_init_msg());
+        mock_proposal(&mut deps, default_proposal_msg());
+        mock_vote(&mut deps, "voter_0".to_string(), 0, coins(100, "earth"));
+        mock_distribute_funds(&mut deps).unwrap();
+
+        match mock_withdraw_vested(&mut deps, 6) {
+            Err(ContractError::VestingDisabled {}) => {}
+            _ => panic!("Must return error"),
+        }
+    }
+
+    #[test]
+    fn fails_initialization_invalid_vesting() {
+        let t = mock_env().block.time;
+        let mut msg = vesting_init_msg();
+        msg.vesting = Some(VestingSchedule {
+            start: Expiration::AtTime(t + 86400 * 5),
+            cliff: Expiration::AtTime(t + 86400 * 200),
+            end: Expiration::AtTime(t + 86400 * 105),
+        });
+        assert_invalid_schedule(msg);
+
+        // vesting must be over before claims close.
+        let mut msg = vesting_init_msg();
+        msg.claim_deadline = Some(Expiration::AtTime(t + 86400 * 50));
+        assert_invalid_schedule(msg);
+    }
+
+    fn milestone_proposal_msg(shares: &[&str]) -> HandleMsg {
+        let milestones = shares
+            .iter()
+            .enumerate()
+            .map(|(i, share)| Milestone {
+                description: format!("milestone {}", i),
+                share: share.parse().unwrap(),
+            })
+            .collect();
+        HandleMsg::CreateProposal {
+            name: "My proposal".to_string(),
+            recipient: HumanAddr::from("proposal_recipient"),
+            description: "The proposal description".to_string(),
+            tags: "one two three".to_string(),
+            milestones: Some(milestones),
+        }
+    }
+
+    fn mock_release_milestone(
+        deps: &mut Extern<MockStorage, MockApi, MockQuerier>,
+        sender: &str,
+        milestone: u32,
+    ) -> Result<HandleResponse, ContractError> {
+        let mut env = mock_env();
+        env.block.time += 86400 * 6;
+        let msg = HandleMsg::ReleaseMilestone {
+            proposal_id: 0,
+            milestone,
+        };
+        handle(deps, env, mock_info(sender, &[]), msg)
+    }
+
+    #[test]
+    fn release_milestones() {
+        let mut deps = mock_dependencies(&coins(1000, "earth"));
+        mock_init(&mut deps, default_init_msg());
+        mock_proposal(&mut deps, milestone_proposal_msg(&["0.25", "0.75"]));
+        mock_vote(&mut deps, "voter_0".to_string(), 0, coins(100, "earth"));
+        mock_vote(&mut deps, "voter_1".to_string(), 0, coins(100, "earth"));
+        mock_distribute_funds(&mut deps).unwrap();
+
+        // everything is held in escrow until a milestone is released.
+        match mock_claim(&mut deps, "proposal_recipient", 0, 6) {
+            Err(ContractError::NothingToClaim {}) => {}
+            _ => panic!("Must return error"),
+        }
+        match mock_release_milestone(&mut deps, "voter_0", 0) {
+            Err(ContractError::Unauthorized { list_type: _ }) => {}
+            _ => panic!("Must return error"),
+        }
+        mock_release_milestone(&mut deps, "reviewer", 0).unwrap();
+        match mock_release_milestone(&mut deps, "owner", 0) {
+            Err(ContractError::MilestoneNotPending { milestone: 0 }) => {}
+            _ => panic!("Must return error"),
+        }
+        match mock_release_milestone(&mut deps, "owner", 2) {
+            Err(ContractError::InvalidMilestones { reason: _ }) => {}
+            _ => panic!("Must return error"),
+        }
+
+        let sent = |res: HandleResponse| match &res.messages[0] {
+            CosmosMsg::Bank(BankMsg::Send { amount, .. }) => amount.clone(),
+            _ => unimplemented!(),
+        };
+        let res = mock_claim(&mut deps, "proposal_recipient", 0, 6).unwrap();
+        assert_eq!(coins(300, "earth"), sent(res));
+
+        // the owner can release milestones too, and the rest can then be claimed.
+        mock_release_milestone(&mut deps, "owner", 1).unwrap();
+        let res = mock_claim(&mut deps, "proposal_recipient", 0, 6).unwrap();
+        assert_eq!(coins(900, "earth"), sent(res));
+        match mock_claim(&mut deps, "proposal_recipient", 0, 6) {
+            Err(ContractError::AlreadyClaimed {}) => {}
+            _ => panic!("Must return error"),
+        }
+    }
+
+    #[test]
+    fn claw_back_milestones() {
+        let mut deps = mock_dependencies(&coins(1000, "earth"));
+        mock_init(&mut deps, default_init_msg());
+        mock_proposal(&mut deps, milestone_proposal_msg(&["0.25", "0.75"]));
+        mock_vote(&mut deps, "voter_0".to_string(), 0, coins(100, "earth"));
+        mock_vote(&mut deps, "voter_1".to_string(), 0, coins(100, "earth"));
+        mock_distribute_funds(&mut deps).unwrap();
+        mock_release_milestone(&mut deps, "reviewer", 0).unwrap();
+
+        let mut env = mock_env();
+        env.block.time += 86400 * 6;
+        let msg = HandleMsg::ClawBack { proposal_id: 0 };
+        let res = handle(
+            &mut deps,
+            env.clone(),
+            mock_info("voter_0", &[]),
+            msg.clone(),
+        );
+        match res {
+            Err(ContractError::Unauthorized { list_type: _ }) => {}
+            _ => panic!("Must return error"),
+        }
+        // the unreleased milestone goes back to the only sponsor, the owner.
+        let res = handle(
+            &mut deps,
+            env.clone(),
+            mock_info("reviewer", &[]),
+            msg.clone(),
+        )
+        .unwrap();
+        assert_eq!(
+            vec![CosmosMsg::Bank(BankMsg::Send {
+                from_address: mock_env().contract.address,
+                to_address: HumanAddr::from("owner"),
+                amount: coins(900, "earth"),
+            })],
+            res.messages
+        );
+        let snapshot = query_snapshot(&deps);
+        assert_eq!(coins(900, "earth"), snapshot.clawed_back);
+        assert_eq!(
+            Some(&LeftoverPayment {
+                recipient: HumanAddr::from("owner"),
+                amount: coins(900, "earth"),
+            }),
+            snapshot.leftover.last()
+        );
+        let res = handle(&mut deps, env, mock_info("owner", &[]), msg);
+        match res {
+            Err(ContractError::NothingToClaim {}) => {}
+            _ => panic!("Must return error"),
+        }
+        match mock_release_milestone(&mut deps, "owner", 1) {
+            Err(ContractError::MilestoneNotPending { milestone: 1 }) => {}
+            _ => panic!("Must return error"),
+        }
+
+        // the released milestone can still be claimed.
+        let res = mock_claim(&mut deps, "proposal_recipient", 0, 6).unwrap();
+        assert_eq!(
+            vec![CosmosMsg::Bank(BankMsg::Send {
+                from_address: mock_env().contract.address,
+                to_address: HumanAddr::from("proposal_recipient"),
+                amount: coins(300, "earth"),
+            })],
+            res.messages
+        );
+    }
+
+    #[test]
+    fn claw_back_follows_leftover_policy() {
+        let mut deps = mock_dependencies(&[]);
+        let mut msg = default_init_msg();
+        msg.leftover_policy = Some(LeftoverPolicy::Treasury {
+            address: HumanAddr::from("treasury"),
+        });
+        mock_init(&mut deps, msg);
+        mock_proposal(&mut deps, milestone_proposal_msg(&["0.25", "0.75"]));
+        mock_vote(&mut deps, "voter_0".to_string(), 0, coins(100, "earth"));
+        mock_vote(&mut deps, "voter_1".to_string(), 0, coins(100, "earth"));
+        mock_distribute_funds(&mut deps).unwrap();
+
+        let mut env = mock_env();
+        env.block.time += 86400 * 6;
+        let msg = HandleMsg::ClawBack { proposal_id: 0 };
+        let res = handle(&mut deps, env, mock_info("reviewer", &[]), msg).unwrap();
+        assert_eq!(
+            vec![CosmosMsg::Bank(BankMsg::Send {
+                from_address: mock_env().contract.address,
+                to_address: HumanAddr::from("treasury"),
+                amount: coins(1200, "earth"),
+            })],
+            res.messages
+        );
+        assert_eq!(coins(1200, "earth"), query_snapshot(&deps).clawed_back);
+    }
+
+    #[test]
+    fn claw_back_without_sponsors() {
+        let mut deps = mock_dependencies(&[]);
+        mock_init_with_pool(&mut deps, default_init_msg(), &[]);
+        mock_proposal(&mut deps, milestone_proposal_msg(&["0.25", "0.75"]));
+        mock_vote(&mut deps, "voter_0".to_string(), 0, coins(100, "earth"));
+        mock_vote(&mut deps, "voter_1".to_string(), 0, coins(100, "earth"));
+        mock_distribute_funds(&mut deps).unwrap();
+
+        // nobody sponsored earth, so the clawed back votes go to the owner.
+        let mut env = mock_env();
+        env.block.time += 86400 * 6;
+        let msg = HandleMsg::ClawBack { proposal_id: 0 };
+        let res = handle(&mut deps, env, mock_info("reviewer", &[]), msg).unwrap();
+        assert_eq!(
+            vec![CosmosMsg::Bank(BankMsg::Send {
+                from_address: mock_env().contract.address,
+                to_address: HumanAddr::from("owner"),
+                amount: coins(200, "earth"),
+            })],
+            res.messages
+        );
+        let snapshot = query_snapshot(&deps);
+        assert_eq!(coins(200, "earth"), snapshot.clawed_back);
+        assert_eq!(
+            vec![LeftoverPayment {
+                recipient: HumanAddr::from("owner"),
+                amount: coins(200, "earth"),
+            }],
+            snapshot.leftover
+        );
+    }
+
+    fn mock_approve_proposal(
+        deps: &mut Extern<MockStorage, MockApi, MockQuerier>,
+        sender: &str,
+        proposal_id: u32,
+    ) -> Result<HandleResponse, ContractError> {
+        let msg = HandleMsg::ApproveProposal { proposal_id };
+        handle(deps, mock_env(), mock_info(sender, &[]), msg)
+    }
+
+    #[test]
+    fn approve_proposals() {
+        let mut deps = mock_dependencies(&[]);
+        let mut msg = default_init_msg();
+        msg.require_approval = true;
+        mock_init(&mut deps, msg);
+        mock_proposal(&mut deps, default_proposal_msg());
+        mock_proposal(&mut deps, default_proposal_msg());
+        assert!(load_proposals(&deps.storage)
+            .unwrap()
+            .iter()
+            .all(|p| !p.approved));
+
+        match mock_approve_proposal(&mut deps, "proposer_0", 0) {
+            Err(ContractError::Unauthorized { list_type }) => assert_eq!("reviewer", list_type),
+            _ => panic!("Must return error"),
+        }
+        mock_approve_proposal(&mut deps, "reviewer", 0).unwrap();
+        match mock_approve_proposal(&mut deps, "owner", 0) {
+            Err(ContractError::AlreadyApproved { id: 0 }) => {}
+            _ => panic!("Must return error"),
+        }
+        match mock_approve_proposal(&mut deps, "owner", 2) {
+            Err(ContractError::InvalidProposal { id: 2 }) => {}
+            _ => panic!("Must return error"),
+        }
+
+        // only the approved proposal accepts votes.
+        let mut env = mock_env();
+        env.block.time += 86400 * 3;
+        let msg = HandleMsg::CreateVote {
+            proposal_id: 1,
+            proof: None,
+        };
+        let info = mock_info("voter_0", &coins(100, "earth"));
+        match handle(&mut deps, env, info, msg) {
+            Err(ContractError::ProposalNotApproved { id: 1 }) => {}
+            _ => panic!("Must return error"),
+        }
+        mock_vote(&mut deps, "voter_0".to_string(), 0, coins(100, "earth"));
+        mock_vote(&mut deps, "voter_1".to_string(), 0, coins(100, "earth"));
+
+        // hypothetical votes are rejected the same way.
+        let msg = QueryMsg::Distributions {
+            hypothetical_vote: Some(HypotheticalVote {
+                voter: HumanAddr::from("voter_2"),
+                proposal_id: 1,
+                amount: coin(100, "earth"),
+            }),
+        };
+        match query(&deps, mock_env(), msg) {
+            Err(StdError::GenericErr { msg, .. }) => {
+                assert_eq!(
+                    ContractError::ProposalNotApproved { id: 1 }.to_string(),
+                    msg
+                )
+            }
+            _ => panic!("Must return error"),
+        }
+
+        // and only approved proposals are matched.
+        mock_distribute_funds(&mut deps).unwrap();
+        let snapshot = query_snapshot(&deps);
+        assert_eq!(
+            vec![0],
+            snapshot
+                .distributions
+                .iter()
+                .map(|d| d.proposal)
+                .collect::<Vec<_>>()
+        );
+        assert_eq!(vec![coins(1200, "earth")], claimable_amounts(&deps));
+    }
+
+    #[test]
+    fn proposals_approved_without_review() {
+        let mut deps = mock_dependencies(&[]);
+        mock_init(&mut deps, default_init_msg());
+        mock_proposal(&mut deps, default_proposal_msg());
+        assert!(load_proposals(&deps.storage).unwrap()[0].approved);
+        match mock_approve_proposal(&mut deps, "reviewer", 0) {
+            Err(ContractError::AlreadyApproved { id: 0 }) => {}
+            _ => panic!("Must return error"),
+        }
+    }
+
+    #[test]
+    fn fail_create_proposal_invalid_milestones() {
+        let mut deps = mock_dependencies(&[]);
+        mock_init(&mut deps, default_init_msg());
+        let info = mock_info("proposer_0", &[]);
+        for shares in [vec!["0.5", "0.4"], vec!["1", "0"]].iter() {
+            let msg = milestone_proposal_msg(shares);
+            match handle(&mut deps, mock_env(), info.clone(), msg) {
+                Err(ContractError::InvalidMilestones { reason: _ }) => {}
+                _ => panic!("Must return error"),
+            }
+        }
+    }
+
+    fn mock_fund_pool(
+        deps: &mut Extern<MockStorage, MockApi, MockQuerier>,
+        sponsor: &str,
+        funds: &[Coin],
+    ) -> Result<HandleResponse, ContractError> {
+        handle(
+            deps,
+            mock_env(),
+            mock_info(sponsor, funds),
+            HandleMsg::FundPool {},
+        )
+    }
+
+    #[test]
+    fn fund_pool() {
+        // the contract also holds a stray send that isn't part of the pool.
+        let mut deps = mock_dependencies(&coins(5000, "earth"));
+        mock_init(&mut deps, default_init_msg());
+        mock_fund_pool(&mut deps, "sponsor_0", &coins(500, "earth")).unwrap();
+        mock_fund_pool(&mut deps, "sponsor_1", &coins(300, "uearth")).unwrap();
+        mock_fund_pool(&mut deps, "sponsor_0", &coins(200, "earth")).unwrap();
+
+        let msg = QueryMsg::Sponsors {
+            start_after: None,
+            limit: Some(2),
+        };
+        let res = query(&deps, mock_env(), msg).unwrap();
+        let value: SponsorsResponse = from_binary(&res).unwrap();
+        assert_eq!(2, value.sponsors.len());
+        assert_eq!(vec![coin(1700, "earth"), coin(300, "uearth")], value.total);
+        let msg = QueryMsg::Sponsors {
+            start_after: value.next_start_after,
+            limit: None,
+        };
+        let res = query(&deps, mock_env(), msg).unwrap();
+        let page: SponsorsResponse = from_binary(&res).unwrap();
+        assert_eq!(None, page.next_start_after);
+        let deposits: Vec<_> = value.sponsors.into_iter().chain(page.sponsors).collect();
+        assert_eq!(3, deposits.len());
+        let deposit = |sponsor: &str| {
+            deposits
+                .iter()
+                .find(|d| d.sponsor.as_str() == sponsor)
+                .map(|d| d.amount.clone())
+        };
+        assert_eq!(Some(coins(1000, "earth")), deposit("owner"));
+        assert_eq!(Some(coins(700, "earth")), deposit("sponsor_0"));
+        assert_eq!(Some(coins(300, "uearth")), deposit("sponsor_1"));
+
+        // only the recorded pool is matched, not the contract balance.
+        mock_proposal(&mut deps, default_proposal_msg());
+        mock_vote(&mut deps, "voter_0".to_string(), 0, coins(100, "earth"));
+        mock_vote(&mut deps, "voter_1".to_string(), 0, coins(100, "earth"));
+        mock_distribute_funds(&mut deps).unwrap();
+        assert_eq!(vec![coins(1900, "earth")], claimable_amounts(&deps));
+    }
+
+    #[test]
+    fn fail_fund_pool() {
+        let mut deps = mock_dependencies(&[]);
+        mock_init(&mut deps, default_init_msg());
+        match mock_fund_pool(&mut deps, "sponsor_0", &[]) {
+            Err(ContractError::NoFunds {}) => {}
+            _ => panic!("Must return error"),
+        }
+        match mock_fund_pool(&mut deps, "sponsor_0", &coins(100, "shell")) {
+            Err(ContractError::WrongDenom { denom: _ }) => {}
+            _ => panic!("Must return error"),
+        }
+
+        // the pool can't change once funds are distributed.
+        mock_distribute_funds(&mut deps).unwrap();
+        match mock_fund_pool(&mut deps, "sponsor_0", &coins(100, "earth")) {
+            Err(ContractError::AlreadyDistributed {}) => {}
+            _ => panic!("Must return error"),
+        }
+    }
+
+    // Asserts that everything held for the round, the pool plus every contribution,
+    // is either claimable or recorded as dust.
+    fn assert_conserved(deps: &Extern<MockStorage, MockApi, MockQuerier>, pool: &[Coin]) {
+        let res = query(deps, mock_env(), QueryMsg::FinalDistributions {}).unwrap();
+        let value: FinalDistributionsResponse = from_binary(&res).unwrap();
+        let held = add_coins(
+            pool.to_vec(),
+            total_contributions(&load_all_votes(&deps.storage).unwrap()),
+        );
+        let accounted = claimable_amounts(deps)
+            .into_iter()
+            .fold(value.snapshot.dust, add_coins);
+        for c in held.iter() {
+            let total: u128 = accounted
+                .iter()
+                .filter(|a| a.denom == c.denom)
+                .map(|a| a.amount.u128())
+                .sum();
+            assert_eq!(c.amount.u128(), total, "{} is not conserved", c.denom);
+        }
+        assert!(accounted
+            .iter()
+            .all(|a| a.amount.is_zero() || held.iter().any(|c| c.denom == a.denom)));
+    }
+
+    #[test]
+    fn distribute_funds_conserves_funds() {
+        let pool = vec![coin(1_000_003, "earth"), coin(777, "uearth")];
+        for formula in [
+            FundingFormula::Quadratic {},
+            FundingFormula::Linear {},
+            FundingFormula::CappedQuadratic {
+                cap: Decimal::percent(40),
+            },
+            FundingFormula::PairwiseBounded {
+                bounds: vec![coin(1_000, "earth"), coin(1_000, "uearth")],
+            },
+        ]
+        .iter()
+        {
+            // the contract balance also holds a stray send, which isn't paid out.
+            let mut deps = mock_dependencies(&coins(5, "earth"));
+            let mut msg = default_init_msg();
+            msg.funding_formula = Some(formula.clone());
+            mock_init_with_pool(&mut deps, msg, &pool[..1]);
+            mock_fund_pool(&mut deps, "sponsor_0", &pool[1..]).unwrap();
+            mock_proposal(&mut deps, default_proposal_msg());
+            mock_proposal(&mut deps, default_proposal_msg());
+            mock_proposal(&mut deps, default_proposal_msg());
+            mock_vote(&mut deps, "voter_0".to_string(), 0, coins(3, "earth"));
+            mock_vote(&mut deps, "voter_1".to_string(), 0, coins(7, "earth"));
+            mock_vote(&mut deps, "voter_2".to_string(), 1, coins(11, "earth"));
+            mock_vote(&mut deps, "voter_0".to_string(), 1, coins(2, "earth"));
+            mock_vote(&mut deps, "voter_0".to_string(), 1, coins(5, "earth"));
+            mock_vote(&mut deps, "voter_1".to_string(), 2, coins(13, "uearth"));
+            mock_vote(&mut deps, "voter_2".to_string(), 2, coins(17, "uearth"));
+            mock_vote(&mut deps, "voter_2".to_string(), 0, coins(19, "uearth"));
+            mock_distribute_funds(&mut deps).unwrap();
+            assert_conserved(&deps, &pool);
+        }
+    }
+
+    #[test]
+    fn distribute_funds_without_pool_in_denom() {
+        // uearth votes aren't matched, but they are still paid back.
+        let mut deps = mock_dependencies(&[]);
+        mock_init(&mut deps, default_init_msg());
+        mock_proposal(&mut deps, default_proposal_msg());
+        mock_proposal(&mut deps, default_proposal_msg());
+        mock_vote(&mut deps, "voter_0".to_string(), 0, coins(100, "earth"));
+        mock_vote(&mut deps, "voter_1".to_string(), 0, coins(100, "earth"));
+        mock_vote(&mut deps, "voter_0".to_string(), 1, coins(100, "uearth"));
+        mock_vote(&mut deps, "voter_1".to_string(), 1, coins(100, "uearth"));
+        mock_distribute_funds(&mut deps).unwrap();
+
+        assert_eq!(
+            vec![coins(1200, "earth"), coins(200, "uearth")],
+            claimable_amounts(&deps)
+        );
+        assert_conserved(&deps, &coins(1000, "earth"));
+    }
+
+    #[test]
+    fn fail_check_conservation() {
+        let (votes, proposals) =
+            mock_distribution_input(&[("voter_0", 0, 30), ("voter_1", 0, 70)], 1);
+        let budget = coins(1000, "earth");
+        let contributions = total_contributions(&votes);
+        let mut result = calculate_distributions(
+            &FundingFormula::Quadratic {},
+            votes,
+            proposals,
+            budget.clone(),
+        )
+        .unwrap();
+        let dust = calculate_dust(&result, &budget).unwrap();
+        check_conservation(&result, &budget, &contributions, &dust).unwrap();
+
+        // paying out one coin more than is held breaks the invariant.
+        result[0].distribution_actual.amount += Uint128(1);
+        match check_conservation(&result, &budget, &contributions, &dust) {
+            Err(ContractError::ConservationViolated { denom }) => assert_eq!("earth", denom),
+            _ => panic!("Must return error"),
+        }
+    }
+
+    // Only 200 of the 2001 earth pool can be matched to the single proposal, so
+    // 1801 is left over, along with the whole uearth pool.
+    fn mock_leftover_round(
+        deps: &mut Extern<MockStorage, MockApi, MockQuerier>,
+        policy: Option<LeftoverPolicy>,
+    ) -> HandleResponse {
+        let mut msg = default_init_msg();
+        msg.funding_formula = Some(FundingFormula::CappedQuadratic {
+            cap: Decimal::percent(10),
+        });
+        msg.leftover_policy = policy;
+        mock_init(deps, msg);
+        mock_fund_pool(deps, "sponsor_0", &coins(700, "earth")).unwrap();
+        mock_fund_pool(deps, "sponsor_1", &[coin(301, "earth"), coin(10, "uearth")]).unwrap();
+        mock_proposal(deps, default_proposal_msg());
+        mock_vote(deps, "voter_0".to_string(), 0, coins(100, "earth"));
+        mock_vote(deps, "voter_1".to_string(), 0, coins(100, "earth"));
+        mock_distribute_funds(deps).unwrap()
+    }
+
+    fn query_snapshot(deps: &Extern<MockStorage, MockApi, MockQuerier>) -> DistributionSnapshot {
+        let res = query(deps, mock_env(), QueryMsg::FinalDistributions {}).unwrap();
+        let value: FinalDistributionsResponse = from_binary(&res).unwrap();
+        value.snapshot
+    }
+
+    #[test]
+    fn leftover_to_sponsors() {
+        let mut deps = mock_dependencies(&[]);
+        let res = mock_leftover_round(&mut deps, None);
+        let snapshot = query_snapshot(&deps);
+        assert_eq!(vec![coin(1801, "earth"), coin(10, "uearth")], snapshot.dust);
+
+        // split by deposit, with the rounding remainder going to the largest sponsor.
+        let leftover = |sponsor: &str| {
+            snapshot
+                .leftover
+                .iter()
+                .find(|p| p.recipient.as_str() == sponsor)
+                .map(|p| p.amount.clone())
+        };
+        assert_eq!(3, snapshot.leftover.len());
+        assert_eq!(Some(coins(901, "earth")), leftover("owner"));
+        assert_eq!(Some(coins(630, "earth")), leftover("sponsor_0"));
+        assert_eq!(
+            Some(vec![coin(270, "earth"), coin(10, "uearth")]),
+            leftover("sponsor_1")
+        );
+        assert_eq!(3, res.messages.len());
+        assert!(res.messages.contains(&CosmosMsg::Bank(BankMsg::Send {
+            from_address: mock_env().contract.address,
+            to_address: HumanAddr::from("sponsor_0"),
+            amount: coins(630, "earth"),
+        })));
+    }
+
+    #[test]
+    fn leftover_to_treasury() {
+        let mut deps = mock_dependencies(&[]);
+        let policy = LeftoverPolicy::Treasury {
+            address: HumanAddr::from("treasury"),
+        };
+        let res = mock_leftover_round(&mut deps, Some(policy));
+        let dust = vec![coin(1801, "earth"), coin(10, "uearth")];
+        assert_eq!(
+            vec![CosmosMsg::Bank(BankMsg::Send {
+                from_address: mock_env().contract.address,
+                to_address: HumanAddr::from("treasury"),
+                amount: dust.clone(),
+            })],
+            res.messages
+        );
+        assert_eq!(
+            vec![LeftoverPayment {
+                recipient: HumanAddr::from("treasury"),
+                amount: dust,
+            }],
+            query_snapshot(&deps).leftover
+        );
+    }
+
+    #[test]
+    fn leftover_rollover() {
+        let mut deps = mock_dependencies(&[]);
+        let policy = LeftoverPolicy::Rollover {
+            round: HumanAddr::from("next_round"),
+        };
+        let res = mock_leftover_round(&mut deps, Some(policy));
+        // leftovers wait for SendRollover, so the successor can't block distribution.
+        assert!(res.messages.is_empty());
+        let dust = vec![coin(1801, "earth"), coin(10, "uearth")];
+        assert_eq!(dust, query_snapshot(&deps).pending_rollover);
+
+        match mock_send_rollover(&mut deps, "voter_0", false) {
+            Err(ContractError::Unauthorized { list_type: _ }) => {}
+            _ => panic!("Must return error"),
+        }
+        // the successor round records the leftovers as a sponsor deposit.
+        let res = mock_send_rollover(&mut deps, "treasurer", false).unwrap();
+        assert_eq!(
+            vec![CosmosMsg::Wasm(WasmMsg::Execute {
+                contract_addr: HumanAddr::from("next_round"),
+                msg: to_binary(&HandleMsg::FundPool {}).unwrap(),
+                send: dust.clone(),
+            })],
+            res.messages
+        );
+        let snapshot = query_snapshot(&deps);
+        assert!(snapshot.pending_rollover.is_empty());
+        assert_eq!(
+            vec![LeftoverPayment {
+                recipient: HumanAddr::from("next_round"),
+                amount: dust,
+            }],
+            snapshot.leftover
+        );
+        match mock_send_rollover(&mut deps, "owner", false) {
+            Err(ContractError::NothingToClaim {}) => {}
+            _ => panic!("Must return error"),
+        }
+    }
+
+    fn mock_send_rollover(
+        deps: &mut Extern<MockStorage, MockApi, MockQuerier>,
+        sender: &str,
+        to_sponsors: bool,
+    ) -> Result<HandleResponse, ContractError> {
+        let mut env = mock_env();
+        env.block.time += 86400 * 6;
+        let msg = HandleMsg::SendRollover { to_sponsors };
+        handle(deps, env, mock_info(sender, &[]), msg)
+    }
+
+    #[test]
+    fn leftover_rollover_rejected_by_successor() {
+        let mut deps = mock_dependencies(&[]);
+        let policy = LeftoverPolicy::Rollover {
+            round: HumanAddr::from("next_round"),
+        };
+        mock_leftover_round(&mut deps, Some(policy));
+        // the round is finalized whatever the successor does with the leftovers.
+        let res = mock_claim(&mut deps, "proposal_recipient", 0, 6).unwrap();
+        assert_eq!(1, res.messages.len());
+
+        // if the successor rejects FundPool, sponsors get the leftovers instead.
+        let res = mock_send_rollover(&mut deps, "treasurer", true).unwrap();
+        assert_eq!(
+            vec![
+                CosmosMsg::Bank(BankMsg::Send {
+                    from_address: mock_env().contract.address,
+                    to_address: HumanAddr::from("owner"),
+                    amount: coins(901, "earth"),
+                }),
+                CosmosMsg::Bank(BankMsg::Send {
+                    from_address: mock_env().contract.address,
+                    to_address: HumanAddr::from("sponsor_1"),
+                    amount: vec![coin(270, "earth"), coin(10, "uearth")],
+                }),
+                CosmosMsg::Bank(BankMsg::Send {
+                    from_address: mock_env().contract.address,
+                    to_address: HumanAddr::from("sponsor_0"),
+                    amount: coins(630, "earth"),
+                }),
+            ],
+            res.messages
+        );
+        assert!(query_snapshot(&deps).pending_rollover.is_empty());
+    }
+
+    #[test]
+    fn send_rollover_without_sponsors() {
+        let mut deps = mock_dependencies(&[]);
+        let mut msg = default_init_msg();
+        msg.leftover_policy = Some(LeftoverPolicy::Rollover {
+            round: HumanAddr::from("next_round"),
+        });
+        mock_init_with_pool(&mut deps, msg, &[]);
+        mock_proposal(&mut deps, milestone_proposal_msg(&["0.25", "0.75"]));
+        mock_vote(&mut deps, "voter_0".to_string(), 0, coins(100, "earth"));
+        mock_vote(&mut deps, "voter_1".to_string(), 0, coins(100, "earth"));
+        mock_distribute_funds(&mut deps).unwrap();
+        let mut env = mock_env();
+        env.block.time += 86400 * 6;
+        let msg = HandleMsg::ClawBack { proposal_id: 0 };
+        handle(&mut deps, env, mock_info("reviewer", &[]), msg).unwrap();
+        assert_eq!(coins(200, "earth"), query_snapshot(&deps).pending_rollover);
+
+        // with no earth sponsor the owner is paid, and nothing is left pending.
+        let res = mock_send_rollover(&mut deps, "treasurer", true).unwrap();
+        assert_eq!(
+            vec![CosmosMsg::Bank(BankMsg::Send {
+                from_address: mock_env().contract.address,
+                to_address: HumanAddr::from("owner"),
+                amount: coins(200, "earth"),
+            })],
+            res.messages
+        );
+        let snapshot = query_snapshot(&deps);
+        assert!(snapshot.pending_rollover.is_empty());
+        assert_eq!(
+            vec![LeftoverPayment {
+                recipient: HumanAddr::from("owner"),
+                amount: coins(200, "earth"),
+            }],
+            snapshot.leftover
+        );
+    }
+
+    #[test]
+    fn no_leftover_without_dust() {
+        let mut deps = mock_dependencies(&[]);
+        mock_init(&mut deps, default_init_msg());
+        mock_proposal(&mut deps, default_proposal_msg());
+        mock_vote(&mut deps, "voter_0".to_string(), 0, coins(100, "earth"));
+        mock_vote(&mut deps, "voter_1".to_string(), 0, coins(100, "earth"));
+        let res = mock_distribute_funds(&mut deps).unwrap();
+        assert!(res.messages.is_empty());
+        assert!(query_snapshot(&deps).leftover.is_empty());
+    }
+
+    #[test]
+    fn fails_initialization_invalid_leftover_address() {
+        let mut deps = mock_dependencies(&[]);
+        let mut msg = default_init_msg();
+        msg.leftover_policy = Some(LeftoverPolicy::Treasury {
+            address: HumanAddr::from(""),
+        });
+        let res = init(&mut deps, mock_env(), mock_info("owner", &[]), msg);
+        match res {
+            Err(ContractError::InvalidAddress { list, address: _ }) => {
+                assert_eq!("leftover_policy", list)
+            }
+            _ => panic!("Must return error"),
+        }
+    }
+
+    fn query_whitelists(
+        deps: &Extern<MockStorage, MockApi, MockQuerier>,
+    ) -> (Vec<HumanAddr>, Vec<HumanAddr>) {
+        let res = query(deps, mock_env(), QueryMsg::GetState {}).unwrap();
+        let value: StateResponse = from_binary(&res).unwrap();
+        (value.proposer_whitelist, value.voter_whitelist)
+    }
+
+    fn mock_proposal_by(
+        deps: &mut Extern<MockStorage, MockApi, MockQuerier>,
+        proposer: &str,
+    ) -> Result<HandleResponse, ContractError> {
+        handle(
+            deps,
+            mock_env(),
+            mock_info(proposer, &[]),
+            default_proposal_msg(),
+        )
+    }
+
+    #[test]
+    fn update_whitelists() {
+        let mut deps = mock_dependencies(&[]);
+        mock_init(&mut deps, default_init_msg());
+
+        let msg = HandleMsg::UpdateProposerWhitelist {
+            add: vec![HumanAddr::from("proposer_3"), HumanAddr::from("proposer_0")],
+            remove: vec![HumanAddr::from("proposer_1"), HumanAddr::from("stranger")],
+        };
+        let res = handle(
+            &mut deps,
+            mock_env(),
+            mock_info("voter_0", &[]),
+            msg.clone(),
+        );
+        match res {
+            Err(ContractError::Unauthorized { list_type: _ }) => {}
+            _ => panic!("Must return error"),
+        }
+        // only the addresses that changed are listed.
+        let res = handle(&mut deps, mock_env(), mock_info("owner", &[]), msg).unwrap();
+        assert_eq!(
+            vec![
+                attr("action", "update proposer whitelist"),
+                attr("added", "proposer_3"),
+                attr("removed", "proposer_1"),
+            ],
+            res.attributes
+        );
+
+        // voters can still change after the proposal period.
+        let mut env = mock_env();
+        env.block.time += 86400 * 2 - 1;
+        let msg = HandleMsg::UpdateVoterWhitelist {
+            add: vec![HumanAddr::from("voter_3")],
+            remove: vec![HumanAddr::from("voter_0")],
+        };
+        handle(&mut deps, env, mock_info("owner", &[]), msg).unwrap();
+
+        let (proposers, voters) = query_whitelists(&deps);
+        assert_eq!(
+            vec![
+                HumanAddr::from("proposer_0"),
+                HumanAddr::from("proposer_2"),
+                HumanAddr::from("proposer_3"),
+            ],
+            proposers
+        );
+        assert_eq!(
+            vec![
+                HumanAddr::from("voter_1"),
+                HumanAddr::from("voter_2"),
+                HumanAddr::from("voter_3"),
+            ],
+            voters
+        );
+
+        // the new proposer can create proposals, the removed one can't.
+        mock_proposal_by(&mut deps, "proposer_3").unwrap();
+        match mock_proposal_by(&mut deps, "proposer_1") {
+            Err(ContractError::Unauthorized { list_type: _ }) => {}
+            _ => panic!("Must return error"),
+        }
+    }
+
+    #[test]
+    fn fail_update_whitelists_after_period() {
+        let mut deps = mock_dependencies(&[]);
+        mock_init(&mut deps, default_init_msg());
+
+        // proposers are frozen once the proposal period ends.
+        let mut env = mock_env();
+        env.block.time += 86400 + 1;
+        let msg = HandleMsg::UpdateProposerWhitelist {
+            add: vec![HumanAddr::from("proposer_3")],
+            remove: vec![],
+        };
+        let res = handle(&mut deps, env, mock_info("owner", &[]), msg);
+        match res {
+            Err(ContractError::InvalidPeriod { period_type: _ }) => {}
+            _ => panic!("Must return error"),
+        }
+
+        // voters are frozen once voting starts.
+        let mut env = mock_env();
+        env.block.time += 86400 * 2;
+        let msg = HandleMsg::UpdateVoterWhitelist {
+            add: vec![HumanAddr::from("voter_3")],
+            remove: vec![],
+        };
+        let res = handle(&mut deps, env, mock_info("owner", &[]), msg);
+        match res {
+            Err(ContractError::InvalidPeriod { period_type: _ }) => {}
+            _ => panic!("Must return error"),
+        }
+    }
+
+    #[test]
+    fn fails_initialization_invalid_whitelist_address() {
+        let mut deps = mock_dependencies(&[]);
+        let mut msg = default_init_msg();
+        // too short for the mock api.
+        msg.voter_whitelist.push(HumanAddr::from("v"));
+        let res = init(&mut deps, mock_env(), mock_info("owner", &[]), msg);
+        match res {
+            Err(ContractError::InvalidAddress { list, address }) => {
+                assert_eq!("voter_whitelist", list);
+                assert_eq!("v", address);
+            }
+            _ => panic!("Must return error"),
+        }
+    }
+
+    #[test]
+    fn empty_whitelist_is_closed() {
+        let mut deps = mock_dependencies(&[]);
+        let mut msg = default_init_msg();
+        msg.proposer_whitelist = vec![];
+        mock_init(&mut deps, msg);
+        match mock_proposal_by(&mut deps, "proposer_0") {
+            Err(ContractError::Unauthorized { list_type: _ }) => {}
+            _ => panic!("Must return error"),
+        }
+    }
+
+    fn query_roles(deps: &Extern<MockStorage, MockApi, MockQuerier>) -> RolesResponse {
+        let res = query(deps, mock_env(), QueryMsg::Roles {}).unwrap();
+        from_binary(&res).unwrap()
+    }
+
+    #[test]
+    fn transfer_ownership() {
+        let mut deps = mock_dependencies(&[]);
+        mock_init(&mut deps, default_init_msg());
+
+        let msg = HandleMsg::ProposeOwner {
+            owner: HumanAddr::from("new_owner"),
+        };
+        let res = handle(
+            &mut deps,
+            mock_env(),
+            mock_info("operator", &[]),
+            msg.clone(),
+        );
+        match res {
+            Err(ContractError::Unauthorized { list_type: _ }) => {}
+            _ => panic!("Must return error"),
+        }
+        handle(&mut deps, mock_env(), mock_info("owner", &[]), msg).unwrap();
+        // the owner doesn't change until the new owner accepts.
+        let roles = query_roles(&deps);
+        assert_eq!(HumanAddr::from("owner"), roles.owner);
+        assert_eq!(Some(HumanAddr::from("new_owner")), roles.pending_owner);
+
+        let msg = HandleMsg::AcceptOwnership {};
+        let res = handle(&mut deps, mock_env(), mock_info("owner", &[]), msg.clone());
+        match res {
+            Err(ContractError::Unauthorized { list_type: _ }) => {}
+            _ => panic!("Must return error"),
+        }
+        handle(&mut deps, mock_env(), mock_info("new_owner", &[]), msg).unwrap();
+        let roles = query_roles(&deps);
+        assert_eq!(HumanAddr::from("new_owner"), roles.owner);
+        assert_eq!(None, roles.pending_owner);
+
+        // the previous owner has no admin rights left.
+        let msg = HandleMsg::CancelRound {};
+        let res = handle(&mut deps, mock_env(), mock_info("owner", &[]), msg.clone());
+        match res {
+            Err(ContractError::Unauthorized { list_type: _ }) => {}
+            _ => panic!("Must return error"),
+        }
+        handle(&mut deps, mock_env(), mock_info("new_owner", &[]), msg).unwrap();
+    }
+
+    #[test]
+    fn roles() {
+        let mut deps = mock_dependencies(&[]);
+        mock_init(&mut deps, empty_period_init_msg());
+        let roles = query_roles(&deps);
+        assert_eq!(vec![HumanAddr::from("operator")], roles.operators);
+        assert_eq!(vec![HumanAddr::from("treasurer")], roles.treasurers);
+        assert_eq!(vec![HumanAddr::from("reviewer")], roles.reviewers);
+
+        // operators change periods, but can't distribute funds.
+        let start = HandleMsg::StartProposalPeriod { at: None };
+        let res = handle(
+            &mut deps,
+            mock_env(),
+            mock_info("treasurer", &[]),
+            start.clone(),
+        );
+        match res {
+            Err(ContractError::Unauthorized { list_type }) => assert_eq!("operator", list_type),
+            _ => panic!("Must return error"),
+        }
+        handle(&mut deps, mock_env(), mock_info("operator", &[]), start).unwrap();
+        mock_proposal(&mut deps, default_proposal_msg());
+        let mut env = mock_env();
+        for msg in [
+            HandleMsg::EndProposalPeriod { at: None },
+            HandleMsg::StartVotingPeriod { at: None },
+        ]
+        .iter()
+        {
+            env.block.time += 1;
+            handle(
+                &mut deps,
+                env.clone(),
+                mock_info("operator", &[]),
+                msg.clone(),
+            )
+            .unwrap();
+        }
+        let info = mock_info("voter_0", &coins(100, "earth"));
+        let msg = HandleMsg::CreateVote {
+            proposal_id: 0,
+            proof: None,
+        };
+        handle(&mut deps, env.clone(), info, msg).unwrap();
+        env.block.time += 1;
+        let msg = HandleMsg::EndVotingPeriod { at: None };
+        handle(&mut deps, env.clone(), mock_info("operator", &[]), msg).unwrap();
+
+        env.block.time += 1;
+        let msg = HandleMsg::DistributeFunds {};
+        let res = handle(
+            &mut deps,
+            env.clone(),
+            mock_info("operator", &[]),
+            msg.clone(),
+        );
+        match res {
+            Err(ContractError::Unauthorized { list_type }) => assert_eq!("treasurer", list_type),
+            _ => panic!("Must return error"),
+        }
+        handle(&mut deps, env, mock_info("treasurer", &[]), msg).unwrap();
+    }
+
+    #[test]
+    fn update_role() {
+        let mut deps = mock_dependencies(&[]);
+        mock_init(&mut deps, default_init_msg());
+        let msg = HandleMsg::UpdateRole {
+            role: Role::Treasurer,
+            add: vec![HumanAddr::from("treasurer_1")],
+            remove: vec![HumanAddr::from("treasurer")],
+        };
+        let res = handle(
+            &mut deps,
+            mock_env(),
+            mock_info("treasurer", &[]),
+            msg.clone(),
+        );
+        match res {
+            Err(ContractError::Unauthorized { list_type: _ }) => {}
+            _ => panic!("Must return error"),
+        }
+        handle(&mut deps, mock_env(), mock_info("owner", &[]), msg).unwrap();
+        assert_eq!(
+            vec![HumanAddr::from("treasurer_1")],
+            query_roles(&deps).treasurers
+        );
+    }
+
+    fn merkle_voters() -> Vec<(HumanAddr, Option<Coin>)> {
+        vec![
+            (HumanAddr::from("member_0"), None),
+            (HumanAddr::from("member_1"), Some(coin(150, "earth"))),
+            (HumanAddr::from("member_2"), None),
+            (HumanAddr::from("member_3"), None),
+            (HumanAddr::from("member_4"), Some(coin(1000, "uearth"))),
+        ]
+    }
+
+    // Proofs for each of `merkle_voters`, and the root of their tree.
+    fn merkle_proofs() -> (String, Vec<VoterProof>) {
+        let voters = merkle_voters();
+        let leaves: Vec<_> = voters
+            .iter()
+            .map(|(voter, cap)| merkle::leaf(voter, cap.as_ref()))
+            .collect();
+        let (root, proofs) = merkle::build(&leaves);
+        let proofs = voters
+            .into_iter()
+            .zip(proofs)
+            .map(|((_, cap), proof)| VoterProof {
+                proof: proof.iter().map(hex::encode).collect(),
+                cap,
+            })
+            .collect();
+        (hex::encode(root), proofs)
+    }
+
+    fn mock_vote_with_proof(
+        deps: &mut Extern<MockStorage, MockApi, MockQuerier>,
+        voter: &str,
+        amount: Coin,
+        proof: VoterProof,
+    ) -> Result<HandleResponse, ContractError> {
+        let mut env = mock_env();
+        env.block.time += 86400 * 3;
+        let msg = HandleMsg::CreateVote {
+            proposal_id: 0,
+            proof: Some(proof),
+        };
+        handle(deps, env, mock_info(voter, &[amount]), msg)
+    }
+
+    #[test]
+    fn merkle_tree_proofs() {
+        let (root, proofs) = merkle_proofs();
+        let root = merkle::parse_hash(&root).unwrap();
+        for ((voter, cap), proof) in merkle_voters().iter().zip(proofs.iter()) {
+            let siblings: Vec<_> = proof
+                .proof
+                .iter()
+                .map(|hash| merkle::parse_hash(hash).unwrap())
+                .collect();
+            assert!(merkle::verify(
+                &root,
+                merkle::leaf(voter, cap.as_ref()),
+                &siblings
+            ));
+            assert!(!merkle::verify(
+                &root,
+                merkle::leaf(&HumanAddr::from("stranger"), cap.as_ref()),
+                &siblings
+            ));
+        }
+        // a single leaf is its own root.
+        let leaf = merkle::leaf(&HumanAddr::from("member_0"), None);
+        assert_eq!((leaf, vec![vec![]]), merkle::build(&[leaf]));
+    }
+
+    #[test]
+    fn vote_with_merkle_proof() {
+        let mut deps = mock_dependencies(&[]);
+        let (root, proofs) = merkle_proofs();
+        let mut msg = default_init_msg();
+        msg.voter_merkle_root = Some(root);
+        mock_init(&mut deps, msg);
+        mock_proposal(&mut deps, default_proposal_msg());
+
+        // members aren't in the whitelist, so they need a proof.
+        let mut env = mock_env();
+        env.block.time += 86400 * 3;
+        let msg = HandleMsg::CreateVote {
+            proposal_id: 0,
+            proof: None,
+        };
+        let res = handle(
+            &mut deps,
+            env,
+            mock_info("member_0", &coins(100, "earth")),
+            msg,
+        );
+        match res {
+            Err(ContractError::Unauthorized { list_type: _ }) => {}
+            _ => panic!("Must return error"),
+        }
+        mock_vote_with_proof(&mut deps, "member_0", coin(100, "earth"), proofs[0].clone()).unwrap();
+        mock_vote_with_proof(&mut deps, "member_2", coin(100, "earth"), proofs[2].clone()).unwrap();
+        // whitelisted voters can still vote without one.
+        mock_vote(&mut deps, "voter_0".to_string(), 0, coins(100, "earth"));
+        assert_eq!(3, load_all_votes(&deps.storage).unwrap().len());
+
+        // a proof only works for the voter it was built for.
+        match mock_vote_with_proof(&mut deps, "member_3", coin(100, "earth"), proofs[0].clone()) {
+            Err(ContractError::InvalidMerkleProof {}) => {}
+            _ => panic!("Must return error"),
+        }
+        // a voter can't drop or raise the cap in their leaf.
+        let mut uncapped = proofs[1].clone();
+        uncapped.cap = None;
+        match mock_vote_with_proof(&mut deps, "member_1", coin(100, "earth"), uncapped) {
+            Err(ContractError::InvalidMerkleProof {}) => {}
+            _ => panic!("Must return error"),
+        }
+    }
+
+    #[test]
+    fn merkle_voter_cap() {
+        let mut deps = mock_dependencies(&[]);
+        let (root, proofs) = merkle_proofs();
+        let mut msg = default_init_msg();
+        msg.voter_merkle_root = Some(root);
+        mock_init(&mut deps, msg);
+        mock_proposal(&mut deps, default_proposal_msg());
+
+        mock_vote_with_proof(&mut deps, "member_1", coin(100, "earth"), proofs[1].clone()).unwrap();
+        // the cap covers every vote by the voter.
+        match mock_vote_with_proof(&mut deps, "member_1", coin(51, "earth"), proofs[1].clone()) {
+            Err(ContractError::AboveCap { cap, denom: _ }) => assert_eq!(Uint128(150), cap),
+            _ => panic!("Must return error"),
+        }
+        mock_vote_with_proof(&mut deps, "member_1", coin(50, "earth"), proofs[1].clone()).unwrap();
+        // and only the capped denom can be used.
+        match mock_vote_with_proof(&mut deps, "member_4", coin(10, "earth"), proofs[4].clone()) {
+            Err(ContractError::WrongDenom { denom: _ }) => {}
+            _ => panic!("Must return error"),
+        }
+    }
+
+    #[test]
+    fn merkle_cap_skipped_for_whitelisted_voters() {
+        let mut deps = mock_dependencies(&[]);
+        let (root, _) = merkle_proofs();
+        let mut msg = default_init_msg();
+        msg.voter_merkle_root = Some(root);
+        msg.voter_whitelist.push(HumanAddr::from("member_1"));
+        mock_init(&mut deps, msg);
+        mock_proposal(&mut deps, default_proposal_msg());
+
+        // a whitelisted voter doesn't need a proof, so their leaf's cap doesn't apply.
+        mock_vote(&mut deps, "member_1".to_string(), 0, coins(200, "earth"));
+        assert_eq!(1, load_all_votes(&deps.storage).unwrap().len());
+    }
+
+    #[test]
+    fn fails_initialization_merkle_root_with_open_voting() {
+        let mut deps = mock_dependencies(&[]);
+        let (root, _) = merkle_proofs();
+        let mut msg = default_init_msg();
+        msg.voter_merkle_root = Some(root);
+        msg.open_voting = true;
+        let res = init(&mut deps, mock_env(), mock_info("owner", &[]), msg);
+        match res {
+            Err(ContractError::MerkleRootWithOpenVoting {}) => {}
+            _ => panic!("Must return error"),
+        }
+    }
+
+    #[test]
+    fn fails_initialization_invalid_merkle_root() {
+        let mut deps = mock_dependencies(&[]);
+        let mut msg = default_init_msg();
+        msg.voter_merkle_root = Some("not a hash".to_string());
+        let res = init(&mut deps, mock_env(), mock_info("owner", &[]), msg);
+        match res {
+            Err(ContractError::InvalidMerkleRoot {}) => {}
+            _ => panic!("Must return error"),
+        }
+    }
+
+    // MockQuerier can't query contracts, so this answers cw4 member queries for
+    // the "group" contract and passes everything else through.
+    struct GroupQuerier {
+        base: MockQuerier,
+        members: BTreeMap<String, u64>,
+    }
+
+    impl Querier for GroupQuerier {
+        fn raw_query(&self, bin_request: &[u8]) -> QuerierResult {
+            let request: QueryRequest<Empty> = from_slice(bin_request).unwrap();
+            match request {
+                QueryRequest::Wasm(WasmQuery::Smart { contract_addr, msg }) => {
+                    if contract_addr.as_str() != "group" {
+                        return SystemResult::Err(SystemError::NoSuchContract {
+                            addr: contract_addr,
+                        });
+                    }
+                    let Cw4QueryMsg::Member { addr, at_height: _ } = from_binary(&msg).unwrap();
+                    let res = MemberResponse {
+                        weight: self.members.get(addr.as_str()).copied(),
+                    };
+                    SystemResult::Ok(ContractResult::Ok(to_binary(&res).unwrap()))
+                }
+                _ => self.base.raw_query(bin_request),
+            }
+        }
+    }
+
+    fn mock_group_dependencies(
+        min_weight: Option<u64>,
+    ) -> Extern<MockStorage, MockApi, GroupQuerier> {
+        let deps = mock_dependencies(&[]);
+        let querier = GroupQuerier {
+            base: deps.querier,
+            members: vec![
+                ("member_0".to_string(), 1),
+                ("member_1".to_string(), 5),
+                ("member_2".to_string(), 0),
+            ]
+            .into_iter()
+            .collect(),
+        };
+        let mut deps = Extern {
+            storage: deps.storage,
+            api: deps.api,
+            querier,
+        };
+        let mut msg = default_init_msg();
+        msg.group = Some(Group {
+            addr: HumanAddr::from("group"),
+            min_weight,
+        });
+        init(
+            &mut deps,
+            mock_env(),
+            mock_info("owner", &coins(1000, "earth")),
+            msg,
+        )
+        .unwrap();
+        deps
+    }
+
+    #[test]
+    fn group_members_can_propose_and_vote() {
+        let mut deps = mock_group_dependencies(None);
+
+        let info = mock_info("member_0", &[]);
+        handle(&mut deps, mock_env(), info, default_proposal_msg()).unwrap();
+        // members with no weight are still members.
+        let info = mock_info("member_2", &[]);
+        handle(&mut deps, mock_env(), info, default_proposal_msg()).unwrap();
+        // whitelisted proposers don't need to be in the group.
+        let info = mock_info("proposer_0", &[]);
+        handle(&mut deps, mock_env(), info, default_proposal_msg()).unwrap();
+        let info = mock_info("stranger", &[]);
+        match handle(&mut deps, mock_env(), info, default_proposal_msg()) {
+            Err(ContractError::Unauthorized { list_type: _ }) => {}
+            _ => panic!("Must return error"),
+        }
+
+        let mut env = mock_env();
+        env.block.time += 86400 * 3;
+        let msg = HandleMsg::CreateVote {
+            proposal_id: 0,
+            proof: None,
+        };
+        let info = mock_info("member_1", &coins(100, "earth"));
+        handle(&mut deps, env.clone(), info, msg.clone()).unwrap();
+        let info = mock_info("voter_0", &coins(100, "earth"));
+        handle(&mut deps, env.clone(), info, msg.clone()).unwrap();
+        let info = mock_info("stranger", &coins(100, "earth"));
+        match handle(&mut deps, env, info, msg) {
+            Err(ContractError::Unauthorized { list_type: _ }) => {}
+            _ => panic!("Must return error"),
+        }
+        assert_eq!(2, load_all_votes(&deps.storage).unwrap().len());
+
+        let res = query(&deps, mock_env(), QueryMsg::GetState {}).unwrap();
+        let value: StateResponse = from_binary(&res).unwrap();
+        assert_eq!(Some(HumanAddr::from("group")), value.group.map(|g| g.addr));
+    }
+
+    #[test]
+    fn group_min_weight() {
+        let mut deps = mock_group_dependencies(Some(2));
+
+        let info = mock_info("member_1", &[]);
+        handle(&mut deps, mock_env(), info, default_proposal_msg()).unwrap();
+        let info = mock_info("member_0", &[]);
+        match handle(&mut deps, mock_env(), info, default_proposal_msg()) {
+            Err(ContractError::Unauthorized { list_type: _ }) => {}
+            _ => panic!("Must return error"),
+        }
+
+        let mut env = mock_env();
+        env.block.time += 86400 * 3;
+        let msg = HandleMsg::CreateVote {
+            proposal_id: 0,
+            proof: None,
+        };
+        let info = mock_info("member_1", &coins(100, "earth"));
+        handle(&mut deps, env.clone(), info, msg.clone()).unwrap();
+        let info = mock_info("member_2", &coins(100, "earth"));
+        match handle(&mut deps, env, info, msg) {
+            Err(ContractError::Unauthorized { list_type: _ }) => {}
+            _ => panic!("Must return error"),
+        }
+    }
+
+    #[test]
+    fn group_query_errors_are_returned() {
+        let mut deps = mock_group_dependencies(None);
+        let mut state = crate::state::config_read(&deps.storage).load().unwrap();
+        state.group = Some(Group {
+            addr: HumanAddr::from("not_a_group"),
+            min_weight: None,
+        });
+        crate::state::config(&mut deps.storage)
+            .save(&state)
+            .unwrap();
+
+        let info = mock_info("member_0", &[]);
+        match handle(&mut deps, mock_env(), info, default_proposal_msg()) {
+            Err(ContractError::Std(_)) => {}
+            _ => panic!("Must return error"),
+        }
+    }
 }
@@ -0,0 +1,66 @@
+use cosmwasm_std::{BlockInfo, Coin};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+use crate::error::ContractError;
+use crate::phase::Expiration;
+
+// Distributions vest linearly from `start` to `end`. Nothing can be withdrawn
+// before `cliff`, after which everything vested since `start` is available.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, JsonSchema)]
+pub struct VestingSchedule {
+    pub start: Expiration,
+    pub cliff: Expiration,
+    pub end: Expiration,
+}
+
+impl VestingSchedule {
+    // Recipients must be able to withdraw everything before claims close.
+    pub fn validate(&self, claim_deadline: Option<Expiration>) -> Result<(), ContractError> {
+        if !self.start.same_kind(&self.cliff) || !self.start.same_kind(&self.end) {
+            return Err(invalid_vesting(
+                "start, cliff and end must all be set by height or all by time",
+            ));
+        }
+        if self.cliff.value() < self.start.value() || self.end.value() < self.cliff.value() {
+            return Err(invalid_vesting("cliff must be between start and end"));
+        }
+        if self.end.value() == self.start.value() {
+            return Err(invalid_vesting("end must be after start"));
+        }
+        if let Some(deadline) = claim_deadline {
+            if !deadline.same_kind(&self.end) || deadline.value() <= self.end.value() {
+                return Err(invalid_vesting("must end before the claim deadline"));
+            }
+        }
+        Ok(())
+    }
+
+    // The part of `total` vested at `block`, rounded down.
+    pub fn vested(&self, total: &[Coin], block: &BlockInfo) -> Vec<Coin> {
+        let now = self.start.now(block).value();
+        let (start, end) = (self.start.value(), self.end.value());
+        total
+            .iter()
+            .map(|c| {
+                let amount = if now < self.cliff.value() {
+                    0u128.into()
+                } else if now >= end {
+                    c.amount
+                } else {
+                    c.amount.multiply_ratio(now - start, end - start)
+                };
+                Coin {
+                    denom: c.denom.clone(),
+                    amount,
+                }
+            })
+            .collect()
+    }
+}
+
+fn invalid_vesting(reason: &str) -> ContractError {
+    ContractError::InvalidSchedule {
+        reason: format!("vesting {}", reason),
+    }
+}